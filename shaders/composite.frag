@@ -0,0 +1,12 @@
+#version 450
+
+layout(location = 0) uniform sampler2D first_sample;
+layout(location = 1) uniform sampler2D second_sample;
+layout(location = 2) uniform float split;
+
+layout(location = 0) out vec4 color;
+
+void main() {
+  vec2 uv = gl_FragCoord.xy / textureSize(first_sample, 0);
+  color = uv.x < split ? texture(first_sample, uv) : texture(second_sample, uv);
+}
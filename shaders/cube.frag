@@ -2,12 +2,16 @@
 
 layout(location = 0) in vec3 v_color;
 layout(location = 1) in vec3 v_position;
+layout(location = 2) in vec4 v_material;
+layout(location = 3) in float v_ao;
 layout(location = 0) out vec3 color;
 layout(location = 1) out vec3 normal;
 layout(location = 2) out vec3 position;
+layout(location = 3) out vec4 material;
 
 void main() {
-  color = v_color;
+  color = v_color * v_ao;
   normal = normalize(cross(dFdx(v_position), dFdy(v_position)));
   position = v_position;
+  material = v_material;
 }
@@ -3,8 +3,12 @@
 layout(location = 0) in vec3 position;
 layout(location = 1) in vec3 color;
 layout(location = 2) in uint face;
+layout(location = 3) in vec4 material;
+layout(location = 4) in vec4 ao;
 layout(location = 0) out vec3 gcolor;
 layout(location = 1) out uint gface;
+layout(location = 2) out vec4 gmaterial;
+layout(location = 3) out vec4 gao;
 
 layout(location = 0) uniform mat4 perspective;
 layout(location = 1) uniform mat4 view_model;
@@ -12,5 +16,7 @@ layout(location = 1) uniform mat4 view_model;
 void main() {
   gcolor = color;
   gface = face;
+  gmaterial = material;
+  gao = ao;
   gl_Position = vec4(position, 0.0);
 }
\ No newline at end of file
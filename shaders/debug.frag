@@ -35,9 +35,9 @@ void main() {
   vec3 cur_position = fetchPosition(ivec2(0, 0));
   vec3 cur_normal = fetchNormal(ivec2(0, 0));
 
-  float position_score;
-  vec3 normal_diff;
-  vec3 color_diff;
+  float position_score = 0.0;
+  vec3 normal_diff = vec3(0.0);
+  vec3 color_diff = vec3(0.0);
 
   for (int i = 0; i < 3; i++) {
     for (int j = 0; j < 3; j++) {
@@ -0,0 +1,23 @@
+#version 450
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 color;
+layout(location = 2) in vec4 material;
+layout(location = 0) out vec3 v_color;
+layout(location = 1) out vec3 v_position;
+layout(location = 2) out vec4 v_material;
+layout(location = 3) out float v_ao;
+
+layout(location = 0) uniform mat4 perspective;
+layout(location = 1) uniform mat4 view_model;
+
+void main() {
+  v_color = color;
+  v_material = material;
+  // Merged quads span many faces, so they go without voxel AO.
+  v_ao = 1.0;
+  vec4 view = view_model * vec4(position, 1.0);
+  gl_Position = perspective * view;
+  // Same eye-space convention as cube.geom.
+  v_position = vec3(view.xy, -view.z);
+}
\ No newline at end of file
@@ -0,0 +1,53 @@
+#version 450
+
+layout(location = 0) uniform sampler2D position_sample;
+layout(location = 1) uniform sampler2D normal_sample;
+layout(location = 2) uniform mat4 projection;
+layout(binding = 0, std140) uniform block {
+  vec4 kernel[64];
+  int kernel_size;
+  float radius;
+  float bias;
+};
+layout(location = 0) out float occlusion;
+
+float hash(vec2 p) {
+  return fract(sin(dot(p, vec2(12.9898, 78.233))) * 43758.5453);
+}
+
+void main() {
+  vec2 uv = gl_FragCoord.xy / textureSize(position_sample, 0);
+  vec3 position = texture(position_sample, uv).xyz;
+  if (position.z <= 0.0) {
+    occlusion = 1.0;
+    return;
+  }
+  // cube.frag derives the normal from screen-space derivatives, which
+  // makes it face away from the eye.
+  vec3 normal = -normalize(texture(normal_sample, uv).xyz);
+
+  // Rotate the kernel per pixel in a 4x4 pattern the blur step averages out.
+  float angle = hash(mod(floor(gl_FragCoord.xy), 4.0)) * 6.2831853;
+  vec3 random = vec3(cos(angle), sin(angle), 0.0);
+  vec3 tangent = random - normal * dot(random, normal);
+  if (dot(tangent, tangent) < 1e-4) {
+    tangent = cross(normal, vec3(0.0, 0.0, 1.0));
+  }
+  tangent = normalize(tangent);
+  mat3 tbn = mat3(tangent, cross(normal, tangent), normal);
+
+  float occluded = 0.0;
+  for (int i = 0; i < kernel_size; i++) {
+    vec3 sample_position = position + tbn * kernel[i].xyz * radius;
+    vec4 clip = projection *
+                vec4(sample_position.xy, -sample_position.z, 1.0);
+    vec2 sample_uv = clip.xy / clip.w * 0.5 + 0.5;
+    float depth = texture(position_sample, sample_uv).z;
+    if (depth <= 0.0) {
+      continue;
+    }
+    float range = smoothstep(0.0, 1.0, radius / abs(position.z - depth));
+    occluded += float(depth <= sample_position.z - bias) * range;
+  }
+  occlusion = 1.0 - occluded / float(max(kernel_size, 1));
+}
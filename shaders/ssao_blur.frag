@@ -0,0 +1,22 @@
+#version 450
+
+layout(location = 0) uniform sampler2D occlusion_sample;
+layout(location = 1) uniform int blur;
+layout(location = 0) out vec4 color;
+
+void main() {
+  ivec2 position = ivec2(gl_FragCoord.xy);
+  ivec2 last = textureSize(occlusion_sample, 0) - 1;
+  float occlusion = texelFetch(occlusion_sample, position, 0).r;
+  if (blur > 0) {
+    occlusion = 0.0;
+    for (int x = -blur; x < blur; x++) {
+      for (int y = -blur; y < blur; y++) {
+        ivec2 offset = clamp(position + ivec2(x, y), ivec2(0), last);
+        occlusion += texelFetch(occlusion_sample, offset, 0).r;
+      }
+    }
+    occlusion /= float(4 * blur * blur);
+  }
+  color = vec4(vec3(occlusion), 1.0);
+}
@@ -1,7 +1,7 @@
 #version 450
 
 layout(location = 0) uniform sampler2D color_sample;
-layout(binding = 0, std140) uniform block { float near, far; };
+layout(binding = 0, std140) uniform block { float near, far, strength; };
 layout(location = 0) out vec4 color;
 
 #define K 10
@@ -24,8 +24,8 @@ vec4 strengthen(vec2 pos, vec2 unit) {
     for (int j = -dv; j <= dv; j++) {
       float len = sqrt(j * j + i * i);
       float f = len / K;
-      vec4 current = texture2D(color_sample, pos + vec2(i, j) * unit);
-      color = clamp(color + sstep(f, current.a) * current, color,
+      vec4 current = texture(color_sample, pos + vec2(i, j) * unit);
+      color = clamp(color + strength * sstep(f, current.a) * current, color,
                     max(color, current));
     }
   }
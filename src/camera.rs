@@ -0,0 +1,553 @@
+use std::{collections::HashSet, fmt, str::FromStr, time::Instant};
+
+use glium::glutin::event::{
+    ElementState, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent,
+};
+
+const ROTATE_SPEED: f32 = 0.008;
+const PAN_SPEED: f32 = 0.0015;
+const ZOOM_STEP: f32 = 0.9;
+const FLY_SPEED: f32 = 20.0;
+pub const MAX_PITCH: f32 = 89.0;
+
+const FOV: f32 = 90.0;
+// Leaves a little room around a framed model.
+const FRAME_MARGIN: f32 = 1.05;
+const NEAR: f32 = 0.1;
+const FAR: f32 = 1024.0;
+// Parallel projections put the eye this far back so the whole scene sits
+// in front of the near plane whatever the zoom.
+const ORTHO_EYE_DISTANCE: f32 = 512.0;
+
+// Classic pixel-art isometric: one pixel down for every two across.
+const ISOMETRIC_YAW: f32 = 45.0;
+const ISOMETRIC_PITCH: f32 = 30.0;
+// Technical-drawing dimetric with axes at 7 and 42 degrees.
+const DIMETRIC_YAW: f32 = 20.705;
+const DIMETRIC_PITCH: f32 = 19.471;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProjectionMode {
+    Perspective,
+    Orthographic,
+    /// Orthographic from a fixed 2:1 isometric angle; orbiting turns the
+    /// view in quarter turns.
+    Isometric,
+    /// Orthographic from a fixed dimetric angle, in quarter turns.
+    Dimetric,
+}
+
+impl ProjectionMode {
+    const ALL: [Self; 4] = [
+        Self::Perspective,
+        Self::Orthographic,
+        Self::Isometric,
+        Self::Dimetric,
+    ];
+
+    pub fn next(self) -> Self {
+        match self {
+            Self::Perspective => Self::Orthographic,
+            Self::Orthographic => Self::Isometric,
+            Self::Isometric => Self::Dimetric,
+            Self::Dimetric => Self::Perspective,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Perspective => "perspective",
+            Self::Orthographic => "orthographic",
+            Self::Isometric => "isometric",
+            Self::Dimetric => "dimetric",
+        }
+    }
+
+    /// Yaw and pitch in degrees for projections that always look from the
+    /// same direction.
+    pub fn fixed_angles(self) -> Option<(f32, f32)> {
+        match self {
+            Self::Isometric => Some((ISOMETRIC_YAW, ISOMETRIC_PITCH)),
+            Self::Dimetric => Some((DIMETRIC_YAW, DIMETRIC_PITCH)),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for ProjectionMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|mode| mode.name() == s)
+            .ok_or_else(|| {
+                format!(
+                    "unknown projection `{}`, expected perspective, orthographic, isometric or dimetric",
+                    s
+                )
+            })
+    }
+}
+
+impl fmt::Display for ProjectionMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Starting view directions; the camera is framed to the model afterwards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraPreset {
+    /// Three-quarter view from above, the viewer's default.
+    Corner,
+    Front,
+    Back,
+    Left,
+    Right,
+    Top,
+}
+
+impl CameraPreset {
+    const ALL: [Self; 6] = [
+        Self::Corner,
+        Self::Front,
+        Self::Back,
+        Self::Left,
+        Self::Right,
+        Self::Top,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Corner => "corner",
+            Self::Front => "front",
+            Self::Back => "back",
+            Self::Left => "left",
+            Self::Right => "right",
+            Self::Top => "top",
+        }
+    }
+
+    pub fn camera(self, projection: ProjectionMode) -> Camera {
+        let mut camera = match self {
+            Self::Corner => Camera::default(),
+            Self::Front => Camera::from_angles(0.0, 0.0),
+            Self::Back => Camera::from_angles(180.0, 0.0),
+            Self::Left => Camera::from_angles(-90.0, 0.0),
+            Self::Right => Camera::from_angles(90.0, 0.0),
+            Self::Top => Camera::from_angles(0.0, MAX_PITCH),
+        };
+        camera.set_projection_mode(projection);
+        camera
+    }
+}
+
+impl FromStr for CameraPreset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|preset| preset.name() == s)
+            .ok_or_else(|| {
+                format!(
+                    "unknown camera `{}`, expected corner, front, back, left, right or top",
+                    s
+                )
+            })
+    }
+}
+
+impl fmt::Display for CameraPreset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Looks at `target` from `distance` away, in the direction given by
+/// `yaw` (around Y, from +Z) and `pitch` (above the XZ plane). Orbiting
+/// rotates around the target, flying rotates around the eye; both keep
+/// the same representation so switching modes doesn't move the view.
+#[derive(Debug, Clone, Copy)]
+pub struct Camera {
+    target: glam::Vec3,
+    distance: f32,
+    yaw: f32,
+    pitch: f32,
+    fov: f32,
+    projection: ProjectionMode,
+    // Box of the framed model, used for the strengthen pass depth range.
+    bounds: Option<(glam::Vec3, glam::Vec3)>,
+}
+
+impl Camera {
+    pub fn look_at(eye: glam::Vec3, target: glam::Vec3) -> Self {
+        let offset = eye - target;
+        let distance = offset.length().max(f32::EPSILON);
+        Self {
+            target,
+            distance,
+            yaw: offset.x.atan2(offset.z),
+            pitch: (offset.y / distance).asin(),
+            fov: FOV,
+            projection: ProjectionMode::Perspective,
+            bounds: None,
+        }
+    }
+
+    /// Vertical field of view in degrees. Parallel projections use it too,
+    /// showing what the perspective view would show at the target.
+    pub fn set_fov(&mut self, fov: f32) {
+        self.fov = fov.clamp(1.0, 179.0);
+    }
+
+    /// Looks at the origin from one unit away, `yaw` and `pitch` in degrees.
+    fn from_angles(yaw: f32, pitch: f32) -> Self {
+        let (yaw, pitch) = (yaw.to_radians(), pitch.to_radians());
+        let offset = glam::vec3(
+            pitch.cos() * yaw.sin(),
+            pitch.sin(),
+            pitch.cos() * yaw.cos(),
+        );
+        Self::look_at(offset, glam::Vec3::ZERO)
+    }
+
+    /// Yaw and pitch in degrees, as set; fixed-angle projections may show
+    /// a different view.
+    pub fn orientation(&self) -> (f32, f32) {
+        (self.yaw.to_degrees(), self.pitch.to_degrees())
+    }
+
+    /// Turns around the target to `yaw` and `pitch` in degrees.
+    pub fn set_orientation(&mut self, yaw: f32, pitch: f32) {
+        let limit = MAX_PITCH.to_radians();
+        self.yaw = yaw.to_radians();
+        self.pitch = pitch.to_radians().clamp(-limit, limit);
+    }
+
+    pub fn distance(&self) -> f32 {
+        self.distance
+    }
+
+    pub fn set_distance(&mut self, distance: f32) {
+        self.distance = distance.max(0.5);
+    }
+
+    pub fn projection_mode(&self) -> ProjectionMode {
+        self.projection
+    }
+
+    pub fn set_projection_mode(&mut self, projection: ProjectionMode) {
+        self.projection = projection;
+    }
+
+    /// Yaw and pitch actually used for the view; fixed-angle projections
+    /// snap the yaw to the nearest quarter turn.
+    fn angles(&self) -> (f32, f32) {
+        match self.projection.fixed_angles() {
+            Some((yaw, pitch)) => {
+                let (yaw, pitch) = (yaw.to_radians(), pitch.to_radians());
+                let quarter = std::f32::consts::FRAC_PI_2;
+                let turns = ((self.yaw - yaw) / quarter).round();
+                (yaw + turns * quarter, pitch)
+            }
+            None => (self.yaw, self.pitch),
+        }
+    }
+
+    fn offset(&self) -> glam::Vec3 {
+        let (yaw, pitch) = self.angles();
+        glam::vec3(
+            pitch.cos() * yaw.sin(),
+            pitch.sin(),
+            pitch.cos() * yaw.cos(),
+        )
+    }
+
+    fn eye_distance(&self) -> f32 {
+        match self.projection {
+            ProjectionMode::Perspective => self.distance,
+            _ => ORTHO_EYE_DISTANCE,
+        }
+    }
+
+    pub fn eye(&self) -> glam::Vec3 {
+        self.target + self.offset() * self.eye_distance()
+    }
+
+    pub fn projection(&self, aspect_ratio: f32) -> glam::Mat4 {
+        let half_fov = (self.fov / 2.0).to_radians();
+        match self.projection {
+            ProjectionMode::Perspective => {
+                glam::Mat4::perspective_rh_gl(half_fov * 2.0, aspect_ratio, NEAR, FAR)
+            }
+            // Match the perspective view's scale at the target.
+            _ => {
+                let half_height = self.distance * half_fov.tan();
+                let half_width = half_height * aspect_ratio;
+                glam::Mat4::orthographic_rh_gl(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    NEAR,
+                    FAR,
+                )
+            }
+        }
+    }
+
+    /// Points the camera at the middle of the box `min..max` and moves it
+    /// back just far enough for the whole box to fit the view, keeping the
+    /// current angles, field of view and projection.
+    pub fn frame(&mut self, min: glam::Vec3, max: glam::Vec3, aspect_ratio: f32) {
+        self.target = (min + max) / 2.0;
+        self.bounds = Some((min, max));
+        let half_height = (self.fov / 2.0).to_radians().tan();
+        let half_width = half_height * aspect_ratio;
+        let (right, up, forward) = (self.right(), self.up(), self.forward());
+        let distance = corners(min, max)
+            .map(|corner| {
+                let offset = corner - self.target;
+                let x = offset.dot(right).abs() * FRAME_MARGIN;
+                let y = offset.dot(up).abs() * FRAME_MARGIN;
+                match self.projection {
+                    // The corner has to fit the frustum at its own depth.
+                    ProjectionMode::Perspective => {
+                        let z = offset.dot(forward);
+                        (x / half_width).max(y / half_height).max(NEAR) - z
+                    }
+                    _ => (x / half_width).max(y / half_height),
+                }
+            })
+            .fold(0.0, f32::max);
+        self.set_distance(distance);
+    }
+
+    /// Distances from the eye bounding the framed model, or the target's
+    /// surroundings if nothing was framed, for the active projection.
+    pub fn depth_range(&self) -> (f32, f32) {
+        let (min, max) = match self.bounds {
+            Some(bounds) => bounds,
+            None => {
+                let eye = self.eye_distance();
+                return ((eye - self.distance).max(0.0), eye + self.distance);
+            }
+        };
+        // Later passes see the distance to the eye, so the far end is the
+        // farthest corner rather than the deepest one.
+        let (eye, forward) = (self.eye(), self.forward());
+        corners(min, max).fold((f32::MAX, 0.0f32), |(near, far), corner| {
+            let offset = corner - eye;
+            (
+                near.min(offset.dot(forward).max(0.0)),
+                far.max(offset.length()),
+            )
+        })
+    }
+
+    pub fn forward(&self) -> glam::Vec3 {
+        -self.offset()
+    }
+
+    pub fn right(&self) -> glam::Vec3 {
+        self.forward().cross(glam::Vec3::Y).normalize()
+    }
+
+    pub fn up(&self) -> glam::Vec3 {
+        self.right().cross(self.forward())
+    }
+
+    pub fn view(&self) -> glam::Mat4 {
+        glam::Mat4::look_at_rh(self.eye(), self.target, glam::Vec3::Y)
+    }
+
+    fn turn(&mut self, yaw: f32, pitch: f32) {
+        let limit = MAX_PITCH.to_radians();
+        self.yaw += yaw;
+        self.pitch = (self.pitch + pitch).clamp(-limit, limit);
+    }
+
+    pub fn orbit(&mut self, yaw: f32, pitch: f32) {
+        self.turn(yaw, pitch);
+    }
+
+    /// Turns around the eye rather than the target.
+    pub fn look(&mut self, yaw: f32, pitch: f32) {
+        let eye = self.eye();
+        self.turn(yaw, pitch);
+        self.target = eye - self.offset() * self.eye_distance();
+    }
+
+    pub fn zoom(&mut self, factor: f32) {
+        self.set_distance(self.distance * factor);
+    }
+
+    pub fn pan(&mut self, right: f32, up: f32) {
+        self.target += (self.right() * right + self.up() * up) * self.distance;
+    }
+
+    pub fn translate(&mut self, delta: glam::Vec3) {
+        self.target += delta;
+    }
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self::look_at(glam::vec3(8.0, 10.0, 8.0), glam::vec3(20.0, 0.0, 20.0))
+    }
+}
+
+fn corners(min: glam::Vec3, max: glam::Vec3) -> impl Iterator<Item = glam::Vec3> {
+    (0..8).map(move |i| {
+        glam::vec3(
+            if i & 1 == 0 { min.x } else { max.x },
+            if i & 2 == 0 { min.y } else { max.y },
+            if i & 4 == 0 { min.z } else { max.z },
+        )
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraMode {
+    /// Drag to rotate around the target, scroll to zoom, right-drag to pan.
+    Orbit,
+    /// WASD to move, Space/Shift for up/down, drag to look around.
+    Fly,
+}
+
+pub struct CameraController {
+    camera: Camera,
+    mode: CameraMode,
+    rotating: bool,
+    panning: bool,
+    cursor: Option<(f64, f64)>,
+    keys: HashSet<VirtualKeyCode>,
+    last_update: Instant,
+}
+
+impl CameraController {
+    pub fn new(camera: Camera) -> Self {
+        Self {
+            camera,
+            mode: CameraMode::Orbit,
+            rotating: false,
+            panning: false,
+            cursor: None,
+            keys: HashSet::new(),
+            last_update: Instant::now(),
+        }
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    /// Updates the camera from a window event. Tab switches between orbit
+    /// and fly mode, P cycles the projection.
+    pub fn handle_event(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::MouseInput { state, button, .. } => {
+                let pressed = *state == ElementState::Pressed;
+                match button {
+                    MouseButton::Left => self.rotating = pressed,
+                    MouseButton::Right | MouseButton::Middle => self.panning = pressed,
+                    _ => (),
+                }
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                let (x, y) = (position.x, position.y);
+                if let Some((last_x, last_y)) = self.cursor.replace((x, y)) {
+                    self.drag((x - last_x) as f32, (y - last_y) as f32);
+                }
+            }
+            WindowEvent::CursorLeft { .. } => self.cursor = None,
+            WindowEvent::MouseWheel { delta, .. } => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / 40.0,
+                };
+                match self.mode {
+                    CameraMode::Orbit => self.camera.zoom(ZOOM_STEP.powf(lines)),
+                    CameraMode::Fly => self.camera.translate(self.camera.forward() * lines),
+                }
+            }
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state,
+                        virtual_keycode: Some(key),
+                        ..
+                    },
+                ..
+            } => match (state, key) {
+                (ElementState::Pressed, VirtualKeyCode::Tab) => {
+                    self.mode = match self.mode {
+                        CameraMode::Orbit => CameraMode::Fly,
+                        CameraMode::Fly => CameraMode::Orbit,
+                    };
+                }
+                (ElementState::Pressed, VirtualKeyCode::P) => {
+                    let projection = self.camera.projection_mode().next();
+                    self.camera.set_projection_mode(projection);
+                }
+                (ElementState::Pressed, key) => {
+                    self.keys.insert(*key);
+                }
+                (ElementState::Released, key) => {
+                    self.keys.remove(key);
+                }
+            },
+            WindowEvent::Focused(false) => {
+                self.keys.clear();
+                self.rotating = false;
+                self.panning = false;
+            }
+            _ => (),
+        }
+    }
+
+    fn drag(&mut self, dx: f32, dy: f32) {
+        if self.rotating {
+            let (yaw, pitch) = (-dx * ROTATE_SPEED, dy * ROTATE_SPEED);
+            match self.mode {
+                CameraMode::Orbit => self.camera.orbit(yaw, pitch),
+                CameraMode::Fly => self.camera.look(yaw, -pitch),
+            }
+        } else if self.panning {
+            self.camera.pan(-dx * PAN_SPEED, dy * PAN_SPEED);
+        }
+    }
+
+    /// Applies held movement keys for the time elapsed since the last call.
+    pub fn update(&mut self) {
+        let now = Instant::now();
+        let dt = (now - self.last_update).as_secs_f32();
+        self.last_update = now;
+        if self.mode != CameraMode::Fly {
+            return;
+        }
+
+        let mut direction = glam::Vec3::ZERO;
+        let bindings = [
+            (VirtualKeyCode::W, self.camera.forward()),
+            (VirtualKeyCode::S, -self.camera.forward()),
+            (VirtualKeyCode::D, self.camera.right()),
+            (VirtualKeyCode::A, -self.camera.right()),
+            (VirtualKeyCode::Space, glam::Vec3::Y),
+            (VirtualKeyCode::LShift, -glam::Vec3::Y),
+        ];
+        for (key, axis) in bindings.iter() {
+            if self.keys.contains(key) {
+                direction += *axis;
+            }
+        }
+        if direction != glam::Vec3::ZERO {
+            self.camera
+                .translate(direction.normalize() * FLY_SPEED * dt);
+        }
+    }
+}
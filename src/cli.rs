@@ -0,0 +1,369 @@
+use std::{fmt, path::PathBuf, str::FromStr};
+
+use crate::{
+    camera::{CameraPreset, ProjectionMode},
+    pipelines::preset::PipelinePreset,
+    world::StorageMode,
+};
+
+pub const USAGE: &str = "\
+usage: retro-cube [options] [model.vox]
+
+options:
+  -s, --size WIDTHxHEIGHT   window or image size (default 800x600)
+  -c, --camera PRESET       corner, front, back, left, right or top
+  -p, --projection MODE     perspective, orthographic, isometric or dimetric
+      --fov DEGREES         vertical field of view, 1 to 179 (default 90)
+  -P, --pipeline PRESET     outline, debug, blur or compare
+  -f, --pipeline-file FILE  build the pass chain from a RON description
+                            instead of a preset
+  -o, --output FILE         render one frame to a PNG file without a window
+  -t, --turntable N         render N evenly spaced yaws into a sprite sheet
+                            at --output instead of a single frame; needs a
+                            perspective or orthographic projection
+      --pitches LIST        comma-separated pitches in degrees, one sheet row
+                            each (default: the camera preset's pitch)
+      --atlas FILE          sprite sheet atlas, .json or .ron (default: the
+                            output path with a .json extension)
+      --storage MODE        keep blocks dense or palette compressed (default
+                            palette)
+      --stats               print how much memory the loaded model takes
+  -l, --lod LEVEL           halve the model's resolution LEVEL times before
+                            showing or exporting it
+  -e, --export FILE         write the loaded model to a .vox file instead of
+                            opening a window
+  -w, --watch-shaders       load shaders from ./shaders at runtime and rebuild
+                            them when the files change
+  -h, --help                show this message
+";
+
+#[derive(Debug)]
+pub enum CliError {
+    Help,
+    MissingValue(String),
+    Invalid(String, String),
+    Unexpected(String),
+    TurntableWithoutOutput,
+    TurntableWithFixedAngles(ProjectionMode),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Help => f.write_str(USAGE),
+            CliError::MissingValue(option) => write!(f, "{} needs a value", option),
+            CliError::Invalid(option, reason) => write!(f, "invalid {}: {}", option, reason),
+            CliError::Unexpected(arg) => write!(f, "unexpected argument `{}`", arg),
+            CliError::TurntableWithoutOutput => f.write_str("--turntable needs --output"),
+            CliError::TurntableWithFixedAngles(projection) => write!(
+                f,
+                "--turntable can't turn the {} projection, which has fixed angles",
+                projection
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub model: PathBuf,
+    pub size: (u32, u32),
+    pub camera: CameraPreset,
+    pub projection: ProjectionMode,
+    /// Field of view to use instead of the camera's default.
+    pub fov: Option<f32>,
+    pub pipeline: PipelinePreset,
+    /// Pipeline description to use instead of `pipeline`.
+    pub pipeline_file: Option<PathBuf>,
+    /// Render headless to this file instead of opening a window.
+    pub output: Option<PathBuf>,
+    /// Number of yaws for a sprite sheet.
+    pub turntable: Option<u32>,
+    pub pitches: Vec<f32>,
+    pub atlas: Option<PathBuf>,
+    pub storage: StorageMode,
+    /// Print the world's memory usage after loading it.
+    pub stats: bool,
+    /// Number of times to halve the model's resolution.
+    pub lod: u32,
+    /// Write the loaded world back out as a `.vox` file.
+    pub export: Option<PathBuf>,
+    /// Load shaders from `shaders/` instead of the embedded copies.
+    pub watch_shaders: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            model: PathBuf::from("assets/test.vox"),
+            size: (800, 600),
+            camera: CameraPreset::Corner,
+            projection: ProjectionMode::Perspective,
+            fov: None,
+            pipeline: PipelinePreset::Outline,
+            pipeline_file: None,
+            output: None,
+            turntable: None,
+            pitches: Vec::new(),
+            atlas: None,
+            storage: StorageMode::Palette,
+            stats: false,
+            lod: 0,
+            export: None,
+            watch_shaders: false,
+        }
+    }
+}
+
+fn value<I: Iterator<Item = String>>(args: &mut I, option: &str) -> Result<String, CliError> {
+    args.next()
+        .ok_or_else(|| CliError::MissingValue(option.to_string()))
+}
+
+fn parse<T: FromStr<Err = String>>(option: &str, value: String) -> Result<T, CliError> {
+    value
+        .parse()
+        .map_err(|reason| CliError::Invalid(option.to_string(), reason))
+}
+
+fn parse_angles(s: &str) -> Result<Vec<f32>, String> {
+    s.split(',')
+        .map(|item| match item.trim().parse::<f32>() {
+            Ok(angle) if angle.is_finite() => Ok(angle),
+            _ => Err(format!("`{}` is not a number", item)),
+        })
+        .collect()
+}
+
+fn parse_size(s: &str) -> Result<(u32, u32), String> {
+    let size = s.split_once('x').and_then(|(width, height)| {
+        Some((width.parse::<u32>().ok()?, height.parse::<u32>().ok()?))
+    });
+    match size {
+        Some((width, height)) if width > 0 && height > 0 => Ok((width, height)),
+        _ => Err(format!("`{}` is not WIDTHxHEIGHT", s)),
+    }
+}
+
+impl Options {
+    /// Parses the arguments after the program name.
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, CliError> {
+        let mut options = Self::default();
+        let mut model = None;
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => return Err(CliError::Help),
+                "-s" | "--size" => {
+                    options.size = parse_size(&value(&mut args, &arg)?)
+                        .map_err(|reason| CliError::Invalid(arg.clone(), reason))?
+                }
+                "-c" | "--camera" => options.camera = parse(&arg, value(&mut args, &arg)?)?,
+                "-p" | "--projection" => options.projection = parse(&arg, value(&mut args, &arg)?)?,
+                "--fov" => {
+                    let fov = value(&mut args, &arg)?;
+                    match fov.parse::<f32>() {
+                        Ok(fov) if (1.0..=179.0).contains(&fov) => options.fov = Some(fov),
+                        _ => {
+                            let reason = format!("`{}` is not between 1 and 179 degrees", fov);
+                            return Err(CliError::Invalid(arg, reason));
+                        }
+                    }
+                }
+                "-P" | "--pipeline" => options.pipeline = parse(&arg, value(&mut args, &arg)?)?,
+                "-f" | "--pipeline-file" => {
+                    options.pipeline_file = Some(PathBuf::from(value(&mut args, &arg)?))
+                }
+                "-o" | "--output" => options.output = Some(PathBuf::from(value(&mut args, &arg)?)),
+                "-t" | "--turntable" => {
+                    let yaws = value(&mut args, &arg)?;
+                    match yaws.parse() {
+                        Ok(yaws) if yaws > 0 => options.turntable = Some(yaws),
+                        _ => {
+                            let reason = format!("`{}` is not a positive count", yaws);
+                            return Err(CliError::Invalid(arg, reason));
+                        }
+                    }
+                }
+                "--pitches" => {
+                    options.pitches = parse_angles(&value(&mut args, &arg)?)
+                        .map_err(|reason| CliError::Invalid(arg.clone(), reason))?
+                }
+                "--atlas" => options.atlas = Some(PathBuf::from(value(&mut args, &arg)?)),
+                "--storage" => options.storage = parse(&arg, value(&mut args, &arg)?)?,
+                "--stats" => options.stats = true,
+                "-l" | "--lod" => {
+                    let level = value(&mut args, &arg)?;
+                    options.lod = level.parse().map_err(|_| {
+                        let reason = format!("`{}` is not a level", level);
+                        CliError::Invalid(arg.clone(), reason)
+                    })?
+                }
+                "-e" | "--export" => options.export = Some(PathBuf::from(value(&mut args, &arg)?)),
+                "-w" | "--watch-shaders" => options.watch_shaders = true,
+                _ if arg.starts_with('-') || model.is_some() => {
+                    return Err(CliError::Unexpected(arg))
+                }
+                _ => model = Some(PathBuf::from(arg)),
+            }
+        }
+        if let Some(model) = model {
+            options.model = model;
+        }
+        if options.turntable.is_some() && options.output.is_none() {
+            return Err(CliError::TurntableWithoutOutput);
+        }
+        if options.turntable.is_some() && options.projection.fixed_angles().is_some() {
+            return Err(CliError::TurntableWithFixedAngles(options.projection));
+        }
+        Ok(options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, CliError> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn invalid(args: &[&str]) -> String {
+        match parse(args) {
+            Err(CliError::Invalid(option, _)) => option,
+            other => panic!("{:?} parsed as {:?}", args, other),
+        }
+    }
+
+    #[test]
+    fn defaults() {
+        let options = parse(&[]).unwrap();
+        assert_eq!(options.model, PathBuf::from("assets/test.vox"));
+        assert_eq!(options.size, (800, 600));
+        assert_eq!(options.camera, CameraPreset::Corner);
+        assert_eq!(options.projection, ProjectionMode::Perspective);
+        assert_eq!(options.pipeline, PipelinePreset::Outline);
+        assert_eq!(options.storage, StorageMode::Palette);
+        assert_eq!(options.lod, 0);
+        assert_eq!(options.fov, None);
+        assert!(options.output.is_none() && options.turntable.is_none());
+        assert!(!options.stats && !options.watch_shaders);
+    }
+
+    #[test]
+    fn every_option() {
+        let options = parse(&[
+            "-s",
+            "320x200",
+            "-c",
+            "top",
+            "-p",
+            "orthographic",
+            "--fov",
+            "45",
+            "-P",
+            "blur",
+            "-f",
+            "chain.ron",
+            "-o",
+            "out.png",
+            "-t",
+            "8",
+            "--pitches",
+            "10, -20,30.5",
+            "--atlas",
+            "out.ron",
+            "--storage",
+            "dense",
+            "--stats",
+            "-l",
+            "2",
+            "-e",
+            "out.vox",
+            "-w",
+            "model.vox",
+        ])
+        .unwrap();
+        assert_eq!(options.model, PathBuf::from("model.vox"));
+        assert_eq!(options.size, (320, 200));
+        assert_eq!(options.camera, CameraPreset::Top);
+        assert_eq!(options.projection, ProjectionMode::Orthographic);
+        assert_eq!(options.fov, Some(45.0));
+        assert_eq!(options.pipeline, PipelinePreset::Blur);
+        assert_eq!(options.pipeline_file, Some(PathBuf::from("chain.ron")));
+        assert_eq!(options.output, Some(PathBuf::from("out.png")));
+        assert_eq!(options.turntable, Some(8));
+        assert_eq!(options.pitches, vec![10.0, -20.0, 30.5]);
+        assert_eq!(options.atlas, Some(PathBuf::from("out.ron")));
+        assert_eq!(options.storage, StorageMode::Dense);
+        assert!(options.stats);
+        assert_eq!(options.lod, 2);
+        assert_eq!(options.export, Some(PathBuf::from("out.vox")));
+        assert!(options.watch_shaders);
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("1x1"), Ok((1, 1)));
+        assert_eq!(parse_size("1920x1080"), Ok((1920, 1080)));
+        for size in ["", "800", "800x", "x600", "0x600", "800x0", "-1x5", "8x6x2"] {
+            assert!(parse_size(size).is_err(), "{:?}", size);
+        }
+        assert_eq!(invalid(&["--size", "big"]), "--size");
+    }
+
+    #[test]
+    fn pitch_lists() {
+        assert_eq!(parse_angles("0"), Ok(vec![0.0]));
+        assert_eq!(parse_angles(" 15 ,-45"), Ok(vec![15.0, -45.0]));
+        for list in ["", "10,", "a,b", "NaN", "10,inf"] {
+            assert!(parse_angles(list).is_err(), "{:?}", list);
+        }
+        assert_eq!(invalid(&["--pitches", "up"]), "--pitches");
+    }
+
+    #[test]
+    fn invalid_values() {
+        assert_eq!(invalid(&["-l", "-1"]), "-l");
+        assert_eq!(invalid(&["--lod", "two"]), "--lod");
+        assert_eq!(invalid(&["-t", "0", "-o", "out.png"]), "-t");
+        assert_eq!(invalid(&["--fov", "0"]), "--fov");
+        assert_eq!(invalid(&["--fov", "180"]), "--fov");
+        assert_eq!(invalid(&["-c", "sideways"]), "-c");
+        assert_eq!(invalid(&["-p", "fisheye"]), "-p");
+        assert_eq!(invalid(&["-P", "sepia"]), "-P");
+        assert_eq!(invalid(&["--storage", "sparse"]), "--storage");
+    }
+
+    #[test]
+    fn usage_errors() {
+        assert!(matches!(parse(&["-h"]), Err(CliError::Help)));
+        assert!(
+            matches!(parse(&["--size"]), Err(CliError::MissingValue(option)) if option == "--size")
+        );
+        assert!(matches!(parse(&["--bogus"]), Err(CliError::Unexpected(arg)) if arg == "--bogus"));
+        assert!(matches!(
+            parse(&["one.vox", "two.vox"]),
+            Err(CliError::Unexpected(arg)) if arg == "two.vox"
+        ));
+    }
+
+    #[test]
+    fn turntable_checks() {
+        assert!(matches!(
+            parse(&["-t", "4"]),
+            Err(CliError::TurntableWithoutOutput)
+        ));
+        for projection in ["isometric", "dimetric"] {
+            assert!(matches!(
+                parse(&["-t", "4", "-o", "out.png", "-p", projection]),
+                Err(CliError::TurntableWithFixedAngles(_))
+            ));
+            // Fixed angles are fine for a single frame.
+            assert!(parse(&["-o", "out.png", "-p", projection]).is_ok());
+        }
+        assert!(parse(&["-t", "4", "-o", "out.png", "-p", "orthographic"]).is_ok());
+    }
+}
@@ -0,0 +1,182 @@
+use std::{ffi::c_void, fs::File, io::BufWriter, path::Path, rc::Rc};
+
+use glium::{backend::Facade, texture::RawImage2d};
+
+use crate::pipelines::Display;
+
+type Egl = khronos_egl::DynamicInstance<khronos_egl::EGL1_5>;
+
+// From EGL_MESA_platform_surfaceless, which khronos-egl doesn't name.
+const PLATFORM_SURFACELESS_MESA: khronos_egl::Enum = 0x31DD;
+
+struct EglBackend {
+    egl: Egl,
+    display: khronos_egl::Display,
+    context: khronos_egl::Context,
+    dimensions: (u32, u32),
+}
+
+unsafe impl glium::backend::Backend for EglBackend {
+    fn swap_buffers(&self) -> Result<(), glium::SwapBuffersError> {
+        Ok(())
+    }
+
+    unsafe fn get_proc_address(&self, symbol: &str) -> *const c_void {
+        self.egl
+            .get_proc_address(symbol)
+            .map_or(std::ptr::null(), |f| f as *const c_void)
+    }
+
+    fn get_framebuffer_dimensions(&self) -> (u32, u32) {
+        self.dimensions
+    }
+
+    fn is_current(&self) -> bool {
+        self.egl.get_current_context() == Some(self.context)
+    }
+
+    unsafe fn make_current(&self) {
+        self.egl
+            .make_current(self.display, None, None, Some(self.context))
+            .expect("failed to make the EGL context current");
+    }
+}
+
+impl Drop for EglBackend {
+    fn drop(&mut self) {
+        let _ = self.egl.make_current(self.display, None, None, None);
+        let _ = self.egl.destroy_context(self.display, self.context);
+        let _ = self.egl.terminate(self.display);
+    }
+}
+
+/// An OpenGL context without a window or display server, using Mesa's
+/// surfaceless EGL platform (llvmpipe when there is no GPU). There is no
+/// default framebuffer, so the last pass has to render offscreen.
+pub struct HeadlessDisplay {
+    context: Rc<glium::backend::Context>,
+    dimensions: (u32, u32),
+}
+
+impl HeadlessDisplay {
+    pub fn new(dimensions: (u32, u32)) -> anyhow::Result<Self> {
+        let egl = unsafe { Egl::load_required() }?;
+        let display = unsafe {
+            egl.get_platform_display(
+                PLATFORM_SURFACELESS_MESA,
+                khronos_egl::DEFAULT_DISPLAY,
+                &[khronos_egl::ATTRIB_NONE],
+            )
+        }?;
+        egl.initialize(display)?;
+        egl.bind_api(khronos_egl::OPENGL_API)?;
+        // EGL_KHR_no_config_context: there are no surfaces to match.
+        let config = unsafe { khronos_egl::Config::from_ptr(std::ptr::null_mut()) };
+        let context = egl.create_context(
+            display,
+            config,
+            None,
+            &[
+                khronos_egl::CONTEXT_MAJOR_VERSION,
+                4,
+                khronos_egl::CONTEXT_MINOR_VERSION,
+                3,
+                khronos_egl::CONTEXT_OPENGL_PROFILE_MASK,
+                khronos_egl::CONTEXT_OPENGL_CORE_PROFILE_BIT,
+                khronos_egl::NONE,
+            ],
+        )?;
+        let backend = EglBackend {
+            egl,
+            display,
+            context,
+            dimensions,
+        };
+        let context = unsafe {
+            glium::backend::Context::new(
+                backend,
+                true,
+                glium::debug::DebugCallbackBehavior::default(),
+            )
+        }?;
+        Ok(Self {
+            context,
+            dimensions,
+        })
+    }
+}
+
+impl Facade for HeadlessDisplay {
+    fn get_context(&self) -> &Rc<glium::backend::Context> {
+        &self.context
+    }
+}
+
+impl Display for HeadlessDisplay {
+    fn get_framebuffer_dimensions(&self) -> (u32, u32) {
+        self.dimensions
+    }
+
+    fn draw(&self) -> glium::Frame {
+        glium::Frame::new(self.context.clone(), self.dimensions)
+    }
+}
+
+/// Top-down RGBA8 pixels, as read back from a texture or assembled from
+/// several of them.
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    data: Vec<u8>,
+}
+
+impl Image {
+    /// A fully transparent image.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            data: vec![0; width as usize * height as usize * 4],
+        }
+    }
+
+    pub fn read(texture: &glium::texture::Texture2d) -> Self {
+        let image: RawImage2d<u8> = texture.read();
+        let row = image.width as usize * 4;
+        // OpenGL rows go bottom to top.
+        let data = image.data.chunks(row).rev().flatten().copied().collect();
+        Self {
+            width: image.width,
+            height: image.height,
+            data,
+        }
+    }
+
+    /// Copies `other` in with its top left corner at `(x, y)`, clipping
+    /// whatever falls outside.
+    pub fn blit(&mut self, other: &Image, x: u32, y: u32) {
+        let width = other.width.min(self.width.saturating_sub(x)) as usize * 4;
+        for row in 0..other.height.min(self.height.saturating_sub(y)) {
+            let src = row as usize * other.width as usize * 4;
+            let dst = ((y + row) as usize * self.width as usize + x as usize) * 4;
+            self.data[dst..dst + width].copy_from_slice(&other.data[src..src + width]);
+        }
+    }
+
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        let mut encoder =
+            png::Encoder::new(BufWriter::new(File::create(path)?), self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&self.data)?;
+        Ok(())
+    }
+}
+
+/// Reads `texture` back and writes it to `path` as an RGBA PNG.
+pub fn save_png<P: AsRef<Path>>(
+    texture: &glium::texture::Texture2d,
+    path: P,
+) -> anyhow::Result<()> {
+    Image::read(texture).save_png(path)
+}
@@ -1,47 +1,312 @@
+use std::path::{Path, PathBuf};
+
 use glium::glutin;
-use pipelines::{ChainablePass, ProcessPass};
+use pipelines::{
+    description::PipelineDescription, gbuffer_pass::MeshMode, preset::PresetPipeline,
+    SurfaceProvider,
+};
 
+mod camera;
+mod cli;
+mod headless;
 mod pipelines;
+mod sprite_sheet;
 mod utils;
+mod vox;
 mod world;
 
+fn load_world(options: &cli::Options) -> world::World {
+    let path = &options.model;
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(err) => {
+            eprintln!("failed to read {}: {}", path.display(), err);
+            std::process::exit(1);
+        }
+    };
+    let mut world = match world::World::from_vox(&data) {
+        Ok(world) => world,
+        Err(err) => {
+            eprintln!("failed to load model: {}", err);
+            eprint!("{}", vox::validate(&data));
+            std::process::exit(1);
+        }
+    };
+    if options.lod > 0 {
+        world = world::Octree::from_world(&world)
+            .lod(options.lod)
+            .to_world();
+    }
+    world.set_storage(options.storage);
+    if options.stats {
+        println!("{}", world.memory_usage());
+    }
+    world
+}
+
+/// The `--camera` preset under `--projection`, before it's framed.
+fn start_camera(options: &cli::Options) -> camera::Camera {
+    let mut camera = options.camera.camera(options.projection);
+    if let Some(fov) = options.fov {
+        camera.set_fov(fov);
+    }
+    camera
+}
+
+fn frame_camera(
+    world: &world::World,
+    mut camera: camera::Camera,
+    (width, height): (u32, u32),
+) -> camera::Camera {
+    if let Some((min, max)) = world.bounds() {
+        camera.frame(
+            glam::Vec3::from(min.map(|v| v as f32)),
+            glam::Vec3::from(max.map(|v| v as f32)),
+            width as f32 / height.max(1) as f32,
+        );
+    }
+    camera
+}
+
+/// Builds the pass chain from `--pipeline-file` if given, otherwise from the
+/// `--pipeline` preset.
+fn create_pipeline<Provider>(
+    display: &dyn pipelines::Display,
+    options: &cli::Options,
+) -> anyhow::Result<PresetPipeline<Provider>>
+where
+    Provider: for<'a> SurfaceProvider<'a> + 'static,
+{
+    match &options.pipeline_file {
+        Some(path) => PresetPipeline::describe(display, &PipelineDescription::load(path)?),
+        None => PresetPipeline::create(display, options.pipeline),
+    }
+}
+
+/// Runs the selected pass chain once into an offscreen texture and saves it.
+fn render_headless(
+    world: &world::World,
+    options: &cli::Options,
+    output: &Path,
+) -> anyhow::Result<()> {
+    let display = headless::HeadlessDisplay::new(options.size)?;
+    let camera = start_camera(options);
+    let camera = frame_camera(world, camera, options.size);
+    let mut pipeline = create_pipeline::<pipelines::OffscreenSurfaceProvider>(&display, options)?;
+    pipeline.set_depth_range(camera.depth_range());
+    let texture = pipeline.process(&display, (world, &camera))?;
+    headless::save_png(texture, output)
+}
+
+/// Renders a turntable of the model into one sheet, every frame through the
+/// same pass chain and at the same distance so the sprites share a scale.
+fn render_sprite_sheet(
+    world: &world::World,
+    options: &cli::Options,
+    yaws: u32,
+    output: &Path,
+) -> anyhow::Result<()> {
+    let display = headless::HeadlessDisplay::new(options.size)?;
+    let mut pipeline = create_pipeline::<pipelines::OffscreenSurfaceProvider>(&display, options)?;
+
+    let start = start_camera(options);
+    let (start_yaw, start_pitch) = start.orientation();
+    let pitches = match options.pitches.as_slice() {
+        [] => vec![start_pitch],
+        pitches => pitches.to_vec(),
+    };
+    let mut sheet = sprite_sheet::SpriteSheet::new(options.size, yaws, &pitches);
+    let mut cameras: Vec<_> = sheet
+        .frames
+        .iter_mut()
+        .map(|frame| {
+            // The camera clamps the pitch; the atlas records what was drawn.
+            frame.pitch = frame.pitch.clamp(-camera::MAX_PITCH, camera::MAX_PITCH);
+            let mut camera = start;
+            camera.set_orientation(start_yaw + frame.yaw, frame.pitch);
+            frame_camera(world, camera, options.size)
+        })
+        .collect();
+    let distance = cameras
+        .iter()
+        .map(camera::Camera::distance)
+        .fold(0.0, f32::max);
+
+    let (width, height) = sheet.size();
+    let mut image = headless::Image::new(width, height);
+    for (frame, camera) in sheet.frames.iter().zip(cameras.iter_mut()) {
+        camera.set_distance(distance);
+        pipeline.set_depth_range(camera.depth_range());
+        let texture = pipeline.process(&display, (world, camera))?;
+        image.blit(&headless::Image::read(texture), frame.x, frame.y);
+    }
+    image.save_png(output)?;
+
+    let atlas = match &options.atlas {
+        Some(atlas) => atlas.clone(),
+        None => output.with_extension("json"),
+    };
+    let name = output
+        .file_name()
+        .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+    sheet.write_atlas(atlas, &name)?;
+    Ok(())
+}
+
+/// Describes the block in the middle of the view, for the window title.
+fn aim_title(octree: &world::Octree, camera: &camera::Camera) -> Option<String> {
+    let hit = octree.raycast(camera.eye().into(), camera.forward().into(), f32::INFINITY)?;
+    let world::WorldPosition(x, y, z) = hit.position;
+    let (r, g, b) = hit.block.rgb();
+    let face = hit.face.map_or_else(
+        || "inside".to_owned(),
+        |face| format!("{:?} face", face).to_lowercase(),
+    );
+    Some(format!(
+        " - aim {},{},{} #{:02x}{:02x}{:02x} ({}, {:.1} away)",
+        x, y, z, r, g, b, face, hit.distance
+    ))
+}
+
 fn main() {
+    let options = match cli::Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(cli::CliError::Help) => {
+            print!("{}", cli::USAGE);
+            return;
+        }
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    if options.watch_shaders {
+        pipelines::shader::watch_directory(PathBuf::from("shaders"));
+    }
+    let world = load_world(&options);
+    if let Some(export) = &options.export {
+        let res = std::fs::File::create(export).and_then(|mut file| world.write_vox(&mut file));
+        if let Err(err) = res {
+            eprintln!("failed to export {}: {}", export.display(), err);
+            std::process::exit(1);
+        }
+        if options.output.is_none() {
+            return;
+        }
+    }
+    if let Some(output) = &options.output {
+        let res = match options.turntable {
+            Some(yaws) => render_sprite_sheet(&world, &options, yaws, output),
+            None => render_headless(&world, &options, output),
+        };
+        if let Err(err) = res {
+            eprintln!("failed to render {}: {:#}", output.display(), err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let event_loop = glutin::event_loop::EventLoop::new();
-    let wb = glutin::window::WindowBuilder::new();
+    let (width, height) = options.size;
+    let wb = glutin::window::WindowBuilder::new()
+        .with_title("retro-cube")
+        .with_inner_size(glutin::dpi::PhysicalSize::new(width, height));
     let cb = glutin::ContextBuilder::new();
     let display = glium::Display::new(wb, cb, &event_loop).unwrap();
-    let world = world::World::from_vox(&include_bytes!("../assets/test.vox")[..]);
-
-    let mut pipeline = pipelines::PassGroup::<
-        pipelines::gbuffer_pass::GBufferRenderer,
-        pipelines::gbuffer_pass::GBufferRendererProvider,
-    >::create(&display)
-    .unwrap()
-    .chain(
-        pipelines::PassGroup::<
-            pipelines::outline_pass::OutlinePass,
-            pipelines::postprocess::PostProcessProvider,
-        >::create(&display)
-        .unwrap(),
-    )
-    .chain(
-        pipelines::PassGroup::<
-            pipelines::postprocess::PostProcessPipeline<pipelines::strengthen_pass::StrengthenPass>,
-            pipelines::DisplaySurfaceProvider,
-        >::create(&display)
-        .unwrap(),
-    );
 
+    let mut pipeline =
+        match create_pipeline::<pipelines::DisplaySurfaceProvider>(&display, &options) {
+            Ok(pipeline) => pipeline,
+            Err(err) => {
+                eprintln!("failed to create the pipeline: {:#}", err);
+                std::process::exit(1);
+            }
+        };
+
+    let camera = start_camera(&options);
+    let camera = frame_camera(&world, camera, display.get_framebuffer_dimensions());
+
+    let mut controller = camera::CameraController::new(camera);
+    let octree = world::Octree::from_world(&world);
+    let mut title = String::new();
+    let mut bypassed = false;
     event_loop.run(move |event, _, control_flow| {
         *control_flow = glutin::event_loop::ControlFlow::Poll;
         match event {
-            glutin::event::Event::WindowEvent { event, .. } => match event {
-                glutin::event::WindowEvent::CloseRequested => {
-                    *control_flow = glutin::event_loop::ControlFlow::Exit;
-                    return;
+            glutin::event::Event::WindowEvent {
+                event: glutin::event::WindowEvent::CloseRequested,
+                ..
+            } => {
+                *control_flow = glutin::event_loop::ControlFlow::Exit;
+                return;
+            }
+            glutin::event::Event::WindowEvent {
+                event:
+                    glutin::event::WindowEvent::KeyboardInput {
+                        input:
+                            glutin::event::KeyboardInput {
+                                state: glutin::event::ElementState::Pressed,
+                                virtual_keycode: Some(glutin::event::VirtualKeyCode::M),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => {
+                let renderer = pipeline.renderer_mut();
+                renderer.set_mode(match renderer.mode() {
+                    MeshMode::Points => MeshMode::Greedy,
+                    MeshMode::Greedy => MeshMode::Points,
+                });
+                return;
+            }
+            glutin::event::Event::WindowEvent {
+                event:
+                    glutin::event::WindowEvent::KeyboardInput {
+                        input:
+                            glutin::event::KeyboardInput {
+                                state: glutin::event::ElementState::Pressed,
+                                virtual_keycode: Some(glutin::event::VirtualKeyCode::B),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => {
+                bypassed = pipeline.toggle_bypass() == Some(false);
+                return;
+            }
+            glutin::event::Event::WindowEvent {
+                event:
+                    glutin::event::WindowEvent::KeyboardInput {
+                        input:
+                            glutin::event::KeyboardInput {
+                                state: glutin::event::ElementState::Pressed,
+                                virtual_keycode:
+                                    Some(
+                                        key @ (glutin::event::VirtualKeyCode::LBracket
+                                        | glutin::event::VirtualKeyCode::RBracket),
+                                    ),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => {
+                if let Some(strengthen) = pipeline.strengthen_mut() {
+                    let step = match key {
+                        glutin::event::VirtualKeyCode::LBracket => -0.25,
+                        _ => 0.25,
+                    };
+                    let strength = strengthen.params().strength() + step;
+                    strengthen.params_mut().set_strength(strength);
                 }
-                _ => return,
-            },
+                return;
+            }
+            glutin::event::Event::WindowEvent { event, .. } => {
+                controller.handle_event(&event);
+                return;
+            }
             glutin::event::Event::NewEvents(cause) => match cause {
                 glutin::event::StartCause::ResumeTimeReached { .. } => (),
                 glutin::event::StartCause::Init => (),
@@ -51,10 +316,36 @@ fn main() {
             _ => return,
         }
 
+        controller.update();
+        pipeline.set_depth_range(controller.camera().depth_range());
         pipeline
-            .process(&display, &world)
+            .process(&display, (&world, controller.camera()))
             .unwrap()
             .swapchains()
             .unwrap();
+
+        let glow = pipeline
+            .strengthen_mut()
+            .map(|strengthen| format!(" - glow {:.2}", strengthen.params().strength()));
+        let renderer = pipeline.renderer_mut();
+        let stats = renderer.stats();
+        let mut next = match renderer.mode() {
+            MeshMode::Points => format!("retro-cube - points: {} faces", stats.faces),
+            MeshMode::Greedy => format!(
+                "retro-cube - greedy: {} quads for {} faces ({:.1}%)",
+                stats.quads,
+                stats.faces,
+                stats.quads as f32 * 100.0 / stats.faces.max(1) as f32
+            ),
+        };
+        next.extend(glow);
+        if bypassed {
+            next.push_str(" - bypassed");
+        }
+        next.extend(aim_title(&octree, controller.camera()));
+        if next != title {
+            display.gl_window().window().set_title(&next);
+            title = next;
+        }
     });
 }
@@ -1,43 +1,154 @@
-use glium::implement_uniform_block;
+use glium::{implement_uniform_block, texture::Texture2d};
 
 use crate::postprocess_shader_program;
 
 use super::{
-    postprocess::{PostProcessPipeline, PostProcessProvider, SimplePostProcessPipeline},
-    PassGroup, SurfaceProvider,
+    postprocess::{PingPongProvider, PostProcessPipeline, SimplePostProcessPipeline},
+    shader::ShaderProgram,
+    Display, Pass, PassGroup, SurfaceProvider,
 };
 
+/// The widest radius `blur.frag` is asked to sample; every texel of it
+/// costs a texture read per fragment.
+pub const MAX_RADIUS: u32 = 32;
+
 #[derive(Debug, Clone, Copy)]
 pub struct BlurBlock {
     direction: [f32; 2],
+    radius: i32,
+    sigma: f32,
 }
 
-implement_uniform_block!(BlurBlock, direction);
+implement_uniform_block!(BlurBlock, direction, radius, sigma);
+
+/// One direction of a Gaussian blur: horizontal, or vertical if `VERTICAL`.
+/// Samples `radius` texels to either side, up to `MAX_RADIUS`, weighted by
+/// `sigma`.
+#[derive(Debug, Clone, Copy)]
+pub struct BlurPass<const VERTICAL: bool> {
+    radius: u32,
+    sigma: f32,
+}
+
+impl<const VERTICAL: bool> BlurPass<VERTICAL> {
+    pub fn set_radius(&mut self, radius: u32) {
+        self.radius = radius.min(MAX_RADIUS);
+    }
+
+    pub fn set_sigma(&mut self, sigma: f32) {
+        self.sigma = sigma.max(0.1);
+    }
+}
 
-pub struct BlurPass<const DIR: bool>;
+impl<const VERTICAL: bool> Default for BlurPass<VERTICAL> {
+    fn default() -> Self {
+        Self {
+            radius: 8,
+            sigma: 4.0,
+        }
+    }
+}
 
-impl<const DIR: bool> SimplePostProcessPipeline for BlurPass<DIR> {
+impl<const VERTICAL: bool> SimplePostProcessPipeline for BlurPass<VERTICAL> {
     type Block = BlurBlock;
 
-    fn load_shader(
-        display: &glium::Display,
-    ) -> Result<glium::Program, glium::ProgramCreationError> {
+    fn load_shader(display: &dyn Display) -> Result<ShaderProgram, glium::ProgramCreationError> {
         postprocess_shader_program!(display, "blur")
     }
 
-    fn get_block() -> Self::Block {
+    fn get_block(&self) -> Self::Block {
         BlurBlock {
-            direction: if DIR { [1.0, 1.0] } else { [1.0, -1.0] },
+            direction: if VERTICAL { [0.0, 1.0] } else { [1.0, 0.0] },
+            radius: self.radius as i32,
+            sigma: self.sigma,
         }
     }
 }
 
-#[allow(dead_code)]
-pub fn create_blur_pass<'pass, Provider: SurfaceProvider<'pass>>(
-    display: &glium::Display,
-) -> anyhow::Result<(
-    PassGroup<PostProcessPipeline<BlurPass<false>>, PostProcessProvider>,
-    PassGroup<PostProcessPipeline<BlurPass<true>>, Provider>,
-)> {
-    Ok((PassGroup::create(display)?, PassGroup::create(display)?))
+type HorizontalBlur = PostProcessPipeline<BlurPass<false>>;
+type VerticalBlur = PostProcessPipeline<BlurPass<true>>;
+
+/// A separable Gaussian blur run `iterations` times. Every pass but the
+/// last draws into a `PingPongProvider`, so any number of iterations only
+/// needs two textures.
+pub struct GaussianBlurPass {
+    horizontal: HorizontalBlur,
+    vertical: VerticalBlur,
+    buffers: PingPongProvider,
+    iterations: u32,
+}
+
+impl GaussianBlurPass {
+    pub fn set_radius(&mut self, radius: u32) {
+        self.horizontal.params_mut().set_radius(radius);
+        self.vertical.params_mut().set_radius(radius);
+    }
+
+    pub fn set_sigma(&mut self, sigma: f32) {
+        self.horizontal.params_mut().set_sigma(sigma);
+        self.vertical.params_mut().set_sigma(sigma);
+    }
+
+    pub fn set_iterations(&mut self, iterations: u32) {
+        self.iterations = iterations.max(1);
+    }
+}
+
+impl<'pass, Provider> Pass<'pass, Provider> for GaussianBlurPass
+where
+    Provider: SurfaceProvider<'pass>,
+{
+    type Input = &'pass Texture2d;
+
+    fn with_provider(
+        display: &dyn Display,
+        provider: Provider,
+    ) -> anyhow::Result<PassGroup<Self, Provider>> {
+        Ok(PassGroup::new(
+            Self {
+                horizontal: HorizontalBlur::new(display)?,
+                vertical: VerticalBlur::new(display)?,
+                buffers: PingPongProvider::new(display)?,
+                iterations: 1,
+            },
+            provider,
+        ))
+    }
+
+    fn process<'surface>(
+        &'pass mut self,
+        display: &'surface dyn Display,
+        surface: &'surface mut <Provider as SurfaceProvider<'pass>>::Surface,
+        input: Self::Input,
+    ) -> anyhow::Result<()> {
+        let (mut target, _) = self.buffers.flip(display)?;
+        <HorizontalBlur as Pass<'_, PingPongProvider>>::process(
+            &mut self.horizontal,
+            display,
+            &mut target,
+            input,
+        )?;
+        for _ in 1..self.iterations {
+            let (mut target, source) = self.buffers.flip(display)?;
+            <VerticalBlur as Pass<'_, PingPongProvider>>::process(
+                &mut self.vertical,
+                display,
+                &mut target,
+                source,
+            )?;
+            let (mut target, source) = self.buffers.flip(display)?;
+            <HorizontalBlur as Pass<'_, PingPongProvider>>::process(
+                &mut self.horizontal,
+                display,
+                &mut target,
+                source,
+            )?;
+        }
+        <VerticalBlur as Pass<'pass, Provider>>::process(
+            &mut self.vertical,
+            display,
+            surface,
+            self.buffers.last(),
+        )
+    }
 }
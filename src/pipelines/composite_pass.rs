@@ -0,0 +1,56 @@
+use glium::{texture::Texture2d, uniform, Surface};
+
+use crate::postprocess_shader_program;
+
+use super::{postprocess::*, shader::ShaderProgram, Display, Pass, PassGroup, SurfaceProvider};
+
+/// Joins the outputs of a `PassFork` side by side: the first texture on
+/// the left half, the second on the right.
+pub struct CompositePass {
+    vertex: glium::VertexBuffer<PostProcessVertex>,
+    program: ShaderProgram,
+    split: f32,
+}
+
+impl<'pass, Provider> Pass<'pass, Provider> for CompositePass
+where
+    Provider: SurfaceProvider<'pass>,
+{
+    type Input = (&'pass Texture2d, &'pass Texture2d);
+
+    fn with_provider(
+        display: &dyn Display,
+        provider: Provider,
+    ) -> anyhow::Result<PassGroup<Self, Provider>> {
+        Ok(PassGroup::new(
+            Self {
+                vertex: PostProcessVertex::get_buffer(display)?,
+                program: postprocess_shader_program!(display, "composite")?,
+                split: 0.5,
+            },
+            provider,
+        ))
+    }
+
+    fn process<'surface>(
+        &'pass mut self,
+        display: &'surface dyn Display,
+        surface: &'surface mut <Provider as SurfaceProvider<'pass>>::Surface,
+        (first, second): Self::Input,
+    ) -> anyhow::Result<()> {
+        self.program.reload(display);
+        let uniforms = uniform! {
+            first_sample: first.sampled(),
+            second_sample: second.sampled(),
+            split: self.split,
+        };
+        surface.draw(
+            self.vertex.slice(..).unwrap(),
+            glium::index::NoIndices(glium::index::PrimitiveType::TriangleStrip),
+            &self.program,
+            &uniforms,
+            &Default::default(),
+        )?;
+        Ok(())
+    }
+}
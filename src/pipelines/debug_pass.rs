@@ -5,13 +5,13 @@ use glium::{uniform, Surface};
 use crate::postprocess_shader_program;
 
 use super::{
-    gbuffer_pass::TextureGroup as GBufferTextureGroup, postprocess::*, Pass, PassGroup,
-    SurfaceProvider,
+    gbuffer_pass::TextureGroup as GBufferTextureGroup, postprocess::*, shader::ShaderProgram,
+    Display, Pass, PassGroup, SurfaceProvider,
 };
 
 pub struct DebugPass {
     vertex: glium::VertexBuffer<PostProcessVertex>,
-    program: glium::Program,
+    program: ShaderProgram,
 }
 
 impl<'pass, Provider> Pass<'pass, Provider> for DebugPass
@@ -20,8 +20,8 @@ where
 {
     type Input = &'pass GBufferTextureGroup;
 
-    fn with_provider<'display>(
-        display: &'display glium::Display,
+    fn with_provider(
+        display: &dyn Display,
         provider: Provider,
     ) -> anyhow::Result<PassGroup<Self, Provider>> {
         Ok(PassGroup::new(
@@ -35,10 +35,11 @@ where
 
     fn process<'surface>(
         &'pass mut self,
-        _display: &'surface glium::Display,
+        display: &'surface dyn Display,
         surface: &'surface mut <Provider as SurfaceProvider<'pass>>::Surface,
         input: Self::Input,
     ) -> anyhow::Result<()> {
+        self.program.reload(display);
         let GBufferTextureGroup {
             color,
             normal,
@@ -0,0 +1,273 @@
+use std::{fs, path::Path};
+
+use anyhow::{anyhow, bail, Context};
+use serde::Deserialize;
+
+use crate::{camera::Camera, world::World};
+
+use super::{
+    blur_pass::{self, GaussianBlurPass},
+    debug_pass::DebugPass,
+    dyn_pass::{DynPass, GBufferAdapter, PassAdapter, TextureFormat},
+    gbuffer_pass::GBufferRenderer,
+    outline_pass::OutlinePass,
+    postprocess::PostProcessPipeline,
+    render_graph::RenderGraph,
+    ssao_pass::SsaoPass,
+    strengthen_pass::StrengthenPass,
+    Display, SurfaceInstance, SurfaceProvider,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum PassKind {
+    /// Reads the G-buffer; see `OutlinePass`.
+    Outline,
+    /// Reads the G-buffer; see `DebugPass`.
+    Debug,
+    /// Glow around the outlines, fading over `range` (near, far). Follows
+    /// the camera's depth range when left out. `strength` scales the glow
+    /// and defaults to 1.
+    Strengthen {
+        #[serde(default)]
+        range: Option<(f32, f32)>,
+        #[serde(default)]
+        strength: Option<f32>,
+    },
+    /// Gaussian blur sampling `radius` texels to either side, at most
+    /// `blur_pass::MAX_RADIUS`, run `iterations` times.
+    Blur {
+        #[serde(default)]
+        radius: Option<u32>,
+        #[serde(default)]
+        sigma: Option<f32>,
+        #[serde(default)]
+        iterations: Option<u32>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct PassDescription {
+    pub pass: PassKind,
+    /// Resources the pass reads: `gbuffer`, one of its attachments such as
+    /// `gbuffer.position`, or another pass's output. Defaults to the output
+    /// of the pass listed before, or `gbuffer` for the first one.
+    #[serde(default)]
+    pub inputs: Vec<String>,
+    /// Defaults to `pass<index>`.
+    #[serde(default)]
+    pub output: Option<String>,
+    #[serde(default)]
+    pub format: TextureFormat,
+}
+
+/// Ambient occlusion multiplied into the G-buffer color; see `SsaoPass`.
+/// Fields left out keep their defaults.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+pub struct SsaoDescription {
+    #[serde(default)]
+    pub kernel_size: Option<usize>,
+    #[serde(default)]
+    pub radius: Option<f32>,
+    #[serde(default)]
+    pub bias: Option<f32>,
+    #[serde(default)]
+    pub blur: Option<u32>,
+}
+
+impl SsaoDescription {
+    fn create(&self, display: &dyn Display) -> anyhow::Result<SsaoPass> {
+        let mut ssao = SsaoPass::new(display)?;
+        if let Some(size) = self.kernel_size {
+            ssao.set_kernel_size(size);
+        }
+        if let Some(radius) = self.radius {
+            ssao.set_radius(radius);
+        }
+        if let Some(bias) = self.bias {
+            ssao.set_bias(bias);
+        }
+        if let Some(blur) = self.blur {
+            ssao.set_blur(blur);
+        }
+        Ok(ssao)
+    }
+}
+
+/// The passes run after the G-buffer, which the render graph puts in
+/// dependency order whatever order they are listed in.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct PipelineDescription {
+    /// Shades the G-buffer before any pass reads it.
+    #[serde(default)]
+    pub ssao: Option<SsaoDescription>,
+    pub passes: Vec<PassDescription>,
+    /// The resource drawn to the screen; defaults to the last pass's output.
+    #[serde(default)]
+    pub output: Option<String>,
+}
+
+impl PipelineDescription {
+    pub fn parse(s: &str) -> anyhow::Result<Self> {
+        ron::Options::default()
+            .with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME)
+            .from_str(s)
+            .map_err(|err| anyhow!("{}", err))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        Self::parse(&source).with_context(|| format!("invalid pipeline {}", path.display()))
+    }
+
+    fn output_of(&self, index: usize) -> String {
+        match &self.passes[index].output {
+            Some(output) => output.clone(),
+            None => format!("pass{}", index),
+        }
+    }
+
+    fn inputs_of(&self, index: usize) -> Vec<String> {
+        match (&self.passes[index].inputs[..], index) {
+            ([], 0) => vec![GBUFFER.to_string()],
+            ([], _) => vec![self.output_of(index - 1)],
+            (inputs, _) => inputs.to_vec(),
+        }
+    }
+}
+
+const GBUFFER: &str = "gbuffer";
+
+fn described_pass(
+    display: &dyn Display,
+    pass: PassKind,
+    inputs: Vec<String>,
+    output: String,
+    format: TextureFormat,
+) -> anyhow::Result<Box<dyn DynPass>> {
+    Ok(match pass {
+        PassKind::Outline => Box::new(PassAdapter::<OutlinePass>::create(
+            display, inputs, output, format,
+        )?),
+        PassKind::Debug => Box::new(PassAdapter::<DebugPass>::create(
+            display, inputs, output, format,
+        )?),
+        PassKind::Strengthen { range, strength } => {
+            let mut adapter = PassAdapter::<PostProcessPipeline<StrengthenPass>>::create(
+                display, inputs, output, format,
+            )?;
+            if let Some(strength) = strength {
+                adapter.pass_mut().params_mut().set_strength(strength);
+            }
+            match range {
+                Some(range) => {
+                    adapter.pass_mut().params_mut().set_depth_range(range);
+                    Box::new(adapter)
+                }
+                None => Box::new(adapter.with_update(|pass, _, camera| {
+                    pass.params_mut().set_depth_range(camera.depth_range());
+                })),
+            }
+        }
+        PassKind::Blur {
+            radius,
+            sigma,
+            iterations,
+        } => {
+            let mut adapter =
+                PassAdapter::<GaussianBlurPass>::create(display, inputs, output, format)?;
+            let blur = adapter.pass_mut();
+            if let Some(radius) = radius {
+                if radius > blur_pass::MAX_RADIUS {
+                    bail!(
+                        "blur radius {} is over the maximum of {}",
+                        radius,
+                        blur_pass::MAX_RADIUS
+                    );
+                }
+                blur.set_radius(radius);
+            }
+            if let Some(sigma) = sigma {
+                blur.set_sigma(sigma);
+            }
+            if let Some(iterations) = iterations {
+                blur.set_iterations(iterations);
+            }
+            Box::new(adapter)
+        }
+    })
+}
+
+/// A pipeline built at runtime from a `PipelineDescription`: the passes
+/// run as a `RenderGraph`, and its output is copied to `Provider`.
+pub struct DescribedPipeline<Provider> {
+    graph: RenderGraph,
+    provider: Provider,
+}
+
+impl<Provider> DescribedPipeline<Provider>
+where
+    Provider: for<'a> SurfaceProvider<'a>,
+{
+    pub fn create(
+        display: &dyn Display,
+        description: &PipelineDescription,
+    ) -> anyhow::Result<Self> {
+        let last = match description.passes.len() {
+            0 => bail!("the pipeline has no passes"),
+            len => len - 1,
+        };
+        let mut gbuffer = GBufferAdapter::create(display, GBUFFER.to_string())?;
+        if let Some(ssao) = &description.ssao {
+            gbuffer = gbuffer.with_ssao(ssao.create(display)?);
+        }
+        let mut passes: Vec<Box<dyn DynPass>> = vec![Box::new(gbuffer)];
+        for (index, desc) in description.passes.iter().enumerate() {
+            passes.push(described_pass(
+                display,
+                desc.pass,
+                description.inputs_of(index),
+                description.output_of(index),
+                desc.format,
+            )?);
+        }
+        let output = match &description.output {
+            Some(output) => output.clone(),
+            None => description.output_of(last),
+        };
+        Ok(Self {
+            graph: RenderGraph::build(passes, &output)?,
+            provider: Provider::new(display)?,
+        })
+    }
+
+    pub fn renderer_mut(&mut self) -> &mut GBufferRenderer {
+        self.graph
+            .find_mut::<GBufferAdapter>()
+            .expect("described pipelines always draw a G-buffer")
+            .renderer_mut()
+    }
+
+    /// The first strengthen pass, if the description has one.
+    pub fn strengthen_mut(&mut self) -> Option<&mut PostProcessPipeline<StrengthenPass>> {
+        self.graph
+            .find_mut::<PassAdapter<PostProcessPipeline<StrengthenPass>>>()
+            .map(PassAdapter::pass_mut)
+    }
+
+    pub fn process<'a>(
+        &'a mut self,
+        display: &'a dyn Display,
+        scene: (&'a World, &'a Camera),
+    ) -> anyhow::Result<<Provider as SurfaceProvider<'a>>::Output> {
+        let texture = self.graph.process(display, scene)?;
+        let mut target = self.provider.get(display)?;
+        glium::Surface::fill(
+            &texture.as_surface(),
+            target.surface(),
+            glium::uniforms::MagnifySamplerFilter::Nearest,
+        );
+        Ok(target.output())
+    }
+}
@@ -0,0 +1,380 @@
+use std::{any::Any, collections::HashMap};
+
+use anyhow::{anyhow, bail};
+use glium::texture::{Texture2d, UncompressedFloatFormat};
+use serde::Deserialize;
+
+use crate::{camera::Camera, world::World};
+
+use super::{
+    gbuffer_pass::{GBufferRenderer, GBufferRendererProvider, TextureGroup as GBufferTextureGroup},
+    ssao_pass::SsaoPass,
+    Display, Pass, ProcessPass, SurfaceProvider,
+};
+
+/// Format of a named texture resource.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+pub enum TextureFormat {
+    Rgba8,
+    /// Enough range to carry depth in alpha, which strengthen relies on.
+    #[default]
+    Rgba16F,
+    Rgba32F,
+}
+
+impl From<TextureFormat> for UncompressedFloatFormat {
+    fn from(format: TextureFormat) -> Self {
+        match format {
+            TextureFormat::Rgba8 => UncompressedFloatFormat::U8U8U8U8,
+            TextureFormat::Rgba16F => UncompressedFloatFormat::F16F16F16F16,
+            TextureFormat::Rgba32F => UncompressedFloatFormat::F32F32F32F32,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceKind {
+    Texture,
+    GBuffer,
+}
+
+/// What a pass writes: enough to allocate it before the pass runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceDesc {
+    Texture(TextureFormat),
+    GBuffer,
+}
+
+impl ResourceDesc {
+    pub fn kind(self) -> ResourceKind {
+        match self {
+            ResourceDesc::Texture(_) => ResourceKind::Texture,
+            ResourceDesc::GBuffer => ResourceKind::GBuffer,
+        }
+    }
+}
+
+pub enum Resource {
+    Texture(Texture2d),
+    GBuffer(Box<GBufferTextureGroup>),
+}
+
+impl Resource {
+    fn new(
+        display: &dyn Display,
+        desc: ResourceDesc,
+        (width, height): (u32, u32),
+    ) -> anyhow::Result<Self> {
+        Ok(match desc {
+            ResourceDesc::Texture(format) => Resource::Texture(Texture2d::empty_with_format(
+                display,
+                format.into(),
+                glium::texture::MipmapsOption::NoMipmap,
+                width,
+                height,
+            )?),
+            ResourceDesc::GBuffer => Resource::GBuffer(Box::new(GBufferTextureGroup::new(
+                display,
+                (width, height),
+            )?)),
+        })
+    }
+}
+
+/// The G-buffer attachments that can be read on their own as
+/// `<gbuffer>.<attachment>`.
+pub const GBUFFER_ATTACHMENTS: [&str; 4] = ["color", "normal", "position", "material"];
+
+fn attachment<'a>(group: &'a GBufferTextureGroup, name: &str) -> Option<&'a Texture2d> {
+    match name {
+        "color" => Some(&group.color),
+        "normal" => Some(&group.normal),
+        "position" => Some(&group.position),
+        "material" => Some(&group.material),
+        _ => None,
+    }
+}
+
+/// Textures shared between dynamic passes. Names are bound to slots of a
+/// pool, and names whose lifetimes don't overlap may share a slot.
+#[derive(Default)]
+pub struct Resources {
+    bindings: HashMap<String, usize>,
+    pool: Vec<Resource>,
+}
+
+impl Resources {
+    pub fn new(bindings: HashMap<String, usize>) -> Self {
+        Self {
+            bindings,
+            pool: Vec::new(),
+        }
+    }
+
+    /// Replaces the pool with fresh resources of `slots` at `dimensions`.
+    pub fn allocate(
+        &mut self,
+        display: &dyn Display,
+        slots: &[ResourceDesc],
+        dimensions: (u32, u32),
+    ) -> anyhow::Result<()> {
+        self.pool = slots
+            .iter()
+            .map(|&desc| Resource::new(display, desc, dimensions))
+            .collect::<anyhow::Result<_>>()?;
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> anyhow::Result<&Resource> {
+        self.bindings
+            .get(name)
+            .and_then(|&slot| self.pool.get(slot))
+            .ok_or_else(|| anyhow!("no resource named `{}`", name))
+    }
+
+    /// Looks up a texture, or a single attachment of a G-buffer as
+    /// `gbuffer.position`.
+    pub fn texture(&self, name: &str) -> anyhow::Result<&Texture2d> {
+        if let Some((group, part)) = name.split_once('.') {
+            return attachment(self.gbuffer(group)?, part)
+                .ok_or_else(|| anyhow!("G-buffer `{}` has no `{}`", group, part));
+        }
+        match self.get(name)? {
+            Resource::Texture(texture) => Ok(texture),
+            Resource::GBuffer(_) => bail!("`{}` is a G-buffer, not a texture", name),
+        }
+    }
+
+    pub fn gbuffer(&self, name: &str) -> anyhow::Result<&GBufferTextureGroup> {
+        match self.get(name)? {
+            Resource::GBuffer(group) => Ok(group),
+            Resource::Texture(..) => bail!("`{}` is a texture, not a G-buffer", name),
+        }
+    }
+}
+
+/// An object-safe pass: reads named resources and renders into another
+/// one, so passes can be picked at runtime and kept in a `Vec`. Whoever
+/// runs it allocates the output from `output_desc` beforehand.
+pub trait DynPass: Any {
+    fn inputs(&self) -> &[String];
+
+    /// What kind of resource every input must be; `None` without inputs.
+    fn input_kind(&self) -> Option<ResourceKind>;
+
+    fn output(&self) -> &str;
+
+    fn output_desc(&self) -> ResourceDesc;
+
+    fn process(
+        &mut self,
+        display: &dyn Display,
+        resources: &Resources,
+        scene: (&World, &Camera),
+    ) -> anyhow::Result<()>;
+}
+
+/// Stands in for the provider of a pass run through `PassAdapter`, which
+/// hands the pass a surface over a named resource instead.
+pub struct ResourceProvider;
+
+impl<'provider> SurfaceProvider<'provider> for ResourceProvider {
+    type Surface = glium::framebuffer::SimpleFrameBuffer<'provider>;
+    type Output = ();
+    type Target = (Self::Surface, ());
+
+    fn new(_display: &dyn Display) -> anyhow::Result<Self> {
+        Ok(Self)
+    }
+
+    fn get(&'provider mut self, _display: &'provider dyn Display) -> anyhow::Result<Self::Target> {
+        bail!("passes behind a ResourceProvider draw into named resources")
+    }
+}
+
+/// Pass inputs that can be looked up in `Resources`.
+pub trait ResourceInput<'a>: Sized {
+    const KIND: ResourceKind;
+
+    fn fetch(resources: &'a Resources, names: &[String]) -> anyhow::Result<Self>;
+}
+
+fn single(names: &[String]) -> anyhow::Result<&str> {
+    match names {
+        [name] => Ok(name),
+        _ => bail!("expected one input, got {}", names.len()),
+    }
+}
+
+impl<'a> ResourceInput<'a> for &'a Texture2d {
+    const KIND: ResourceKind = ResourceKind::Texture;
+
+    fn fetch(resources: &'a Resources, names: &[String]) -> anyhow::Result<Self> {
+        resources.texture(single(names)?)
+    }
+}
+
+impl<'a> ResourceInput<'a> for &'a GBufferTextureGroup {
+    const KIND: ResourceKind = ResourceKind::GBuffer;
+
+    fn fetch(resources: &'a Resources, names: &[String]) -> anyhow::Result<Self> {
+        resources.gbuffer(single(names)?)
+    }
+}
+
+type Update<ThisPass> = Box<dyn FnMut(&mut ThisPass, &World, &Camera)>;
+
+/// Runs a typed `Pass` such as `OutlinePass`, `DebugPass` or
+/// `PostProcessPipeline<T>` as a `DynPass`.
+pub struct PassAdapter<ThisPass> {
+    pass: ThisPass,
+    inputs: Vec<String>,
+    output: String,
+    format: TextureFormat,
+    update: Option<Update<ThisPass>>,
+}
+
+impl<ThisPass> PassAdapter<ThisPass>
+where
+    ThisPass: for<'a> Pass<'a, ResourceProvider>,
+{
+    pub fn create(
+        display: &dyn Display,
+        inputs: Vec<String>,
+        output: String,
+        format: TextureFormat,
+    ) -> anyhow::Result<Self> {
+        if inputs.contains(&output) {
+            bail!("`{}` can't be both an input and the output", output);
+        }
+        Ok(Self {
+            pass: ThisPass::with_provider(display, ResourceProvider)?.into_pass(),
+            inputs,
+            output,
+            format,
+            update: None,
+        })
+    }
+
+    /// Calls `update` on the pass before every frame, e.g. to set uniforms
+    /// from the camera.
+    pub fn with_update<F>(mut self, update: F) -> Self
+    where
+        F: FnMut(&mut ThisPass, &World, &Camera) + 'static,
+    {
+        self.update = Some(Box::new(update));
+        self
+    }
+
+    pub fn pass_mut(&mut self) -> &mut ThisPass {
+        &mut self.pass
+    }
+}
+
+impl<ThisPass> DynPass for PassAdapter<ThisPass>
+where
+    ThisPass: for<'a> Pass<'a, ResourceProvider> + 'static,
+    for<'a> <ThisPass as Pass<'a, ResourceProvider>>::Input: ResourceInput<'a>,
+{
+    fn inputs(&self) -> &[String] {
+        &self.inputs
+    }
+
+    fn input_kind(&self) -> Option<ResourceKind> {
+        Some(<<ThisPass as Pass<'static, ResourceProvider>>::Input as ResourceInput>::KIND)
+    }
+
+    fn output(&self) -> &str {
+        &self.output
+    }
+
+    fn output_desc(&self) -> ResourceDesc {
+        ResourceDesc::Texture(self.format)
+    }
+
+    fn process(
+        &mut self,
+        display: &dyn Display,
+        resources: &Resources,
+        (world, camera): (&World, &Camera),
+    ) -> anyhow::Result<()> {
+        if let Some(update) = &mut self.update {
+            update(&mut self.pass, world, camera);
+        }
+        let input = ResourceInput::fetch(resources, &self.inputs)?;
+        let mut surface =
+            glium::framebuffer::SimpleFrameBuffer::new(display, resources.texture(&self.output)?)?;
+        <ThisPass as Pass<'_, ResourceProvider>>::process(
+            &mut self.pass,
+            display,
+            &mut surface,
+            input,
+        )
+    }
+}
+
+/// Draws the scene into a named G-buffer; the start of every render graph.
+pub struct GBufferAdapter {
+    renderer: GBufferRenderer,
+    ssao: Option<SsaoPass>,
+    output: String,
+}
+
+impl GBufferAdapter {
+    pub fn create(display: &dyn Display, output: String) -> anyhow::Result<Self> {
+        Ok(Self {
+            renderer: GBufferRenderer::new(display)?,
+            ssao: None,
+            output,
+        })
+    }
+
+    /// Shades the G-buffer with `ssao` right after drawing it.
+    pub fn with_ssao(mut self, ssao: SsaoPass) -> Self {
+        self.ssao = Some(ssao);
+        self
+    }
+
+    pub fn renderer_mut(&mut self) -> &mut GBufferRenderer {
+        &mut self.renderer
+    }
+}
+
+impl DynPass for GBufferAdapter {
+    fn inputs(&self) -> &[String] {
+        &[]
+    }
+
+    fn input_kind(&self) -> Option<ResourceKind> {
+        None
+    }
+
+    fn output(&self) -> &str {
+        &self.output
+    }
+
+    fn output_desc(&self) -> ResourceDesc {
+        ResourceDesc::GBuffer
+    }
+
+    fn process(
+        &mut self,
+        display: &dyn Display,
+        resources: &Resources,
+        scene: (&World, &Camera),
+    ) -> anyhow::Result<()> {
+        let gbuffer = resources.gbuffer(&self.output)?;
+        <GBufferRenderer as Pass<'_, GBufferRendererProvider>>::process(
+            &mut self.renderer,
+            display,
+            &mut gbuffer.as_surface(display)?,
+            scene,
+        )?;
+        if let Some(ssao) = &mut self.ssao {
+            let (width, height) = display.get_framebuffer_dimensions();
+            ssao.set_projection(scene.1.projection(width as f32 / height as f32));
+            ssao.process(display, gbuffer)?;
+        }
+        Ok(())
+    }
+}
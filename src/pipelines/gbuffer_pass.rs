@@ -1,20 +1,29 @@
-use glium::{buffer::WriteMapping, implement_vertex, uniform, Surface};
+use glium::{implement_vertex, uniform, Surface};
 
 use crate::{
+    camera::Camera,
     shader_program,
-    world::{self, WorldPosition},
+    world::{self, ChunkPosition, WorldPosition},
 };
 
-use super::{Pass, PassGroup, SurfaceProvider};
+use super::{
+    greedy_mesh::{GreedyMesh, MeshStats, QuadVertex, CORNERS},
+    mesh_cache::MeshCache,
+    shader::ShaderProgram,
+    Display, Pass, PassGroup, SurfaceProvider,
+};
 
 #[derive(Copy, Clone)]
-struct FaceInfo {
-    position: [f32; 3],
-    color: [f32; 3],
-    face: u32,
+pub(super) struct FaceInfo {
+    pub(super) position: [f32; 3],
+    pub(super) color: [f32; 3],
+    pub(super) face: u32,
+    pub(super) material: [f32; 4],
+    // Brightness of each corner, in the order cube.geom emits them.
+    ao: [f32; 4],
 }
 
-implement_vertex!(FaceInfo, position, color, face);
+implement_vertex!(FaceInfo, position, color, face, material, ao);
 
 #[derive(Copy, Clone)]
 struct Projection {
@@ -23,13 +32,10 @@ struct Projection {
 }
 
 impl Projection {
-    fn new(aspect_ratio: f32, eye: glam::Vec3, center: glam::Vec3) -> Self {
-        let perspective =
-            glam::Mat4::perspective_rh_gl(f32::to_radians(90.0), aspect_ratio, 0.1, 1024.0);
-        let view_model = glam::Mat4::look_at_rh(eye, center, glam::vec3(0.0, 1.0, 0.0));
+    fn new(aspect_ratio: f32, camera: &Camera) -> Self {
         Self {
-            perspective,
-            view_model,
+            perspective: camera.projection(aspect_ratio),
+            view_model: camera.view(),
         }
     }
 
@@ -41,78 +47,31 @@ impl Projection {
     }
 }
 
-const CUBES: [FaceInfo; 12] = [
-    FaceInfo {
-        position: [-1.0, 0.0, 0.0],
-        color: [0.0, 1.0, 0.0],
-        face: 3,
-    },
-    FaceInfo {
-        position: [-1.0, 0.0, 0.0],
-        color: [0.0, 1.0, 0.0],
-        face: 1,
-    },
-    FaceInfo {
-        position: [-1.0, 0.0, 0.0],
-        color: [0.0, 1.0, 0.0],
-        face: 5,
-    },
-    FaceInfo {
-        position: [0.0, 0.0, 0.0],
-        color: [0.0, 1.0, 0.0],
-        face: 3,
-    },
-    FaceInfo {
-        position: [0.0, 0.0, 0.0],
-        color: [1.0, 0.0, 1.0],
-        face: 1,
-    },
-    FaceInfo {
-        position: [0.0, 0.0, 0.0],
-        color: [0.0, 1.0, 0.0],
-        face: 5,
-    },
-    FaceInfo {
-        position: [0.0, -1.0, 0.0],
-        color: [0.0, 1.0, 0.0],
-        face: 3,
-    },
-    FaceInfo {
-        position: [0.0, -1.0, 0.0],
-        color: [0.0, 1.0, 0.0],
-        face: 1,
-    },
-    FaceInfo {
-        position: [0.0, -1.0, 0.0],
-        color: [0.0, 1.0, 0.0],
-        face: 5,
-    },
-    FaceInfo {
-        position: [0.0, -1.0, -1.0],
-        color: [0.0, 1.0, 0.0],
-        face: 3,
-    },
-    FaceInfo {
-        position: [0.0, -1.0, -1.0],
-        color: [0.0, 1.0, 0.0],
-        face: 1,
-    },
-    FaceInfo {
-        position: [0.0, -1.0, -1.0],
-        color: [0.0, 1.0, 0.0],
-        face: 5,
-    },
-];
+const MIN_FACES: usize = 1024;
+
+/// Picks the face buffer capacity for `needed` faces. Grows to the next
+/// power of two, and only shrinks once usage falls below a quarter so a
+/// scene hovering around a boundary doesn't reallocate every edit.
+fn face_capacity(current: usize, needed: usize) -> usize {
+    if needed > current || needed < current / 4 {
+        needed.next_power_of_two().max(MIN_FACES)
+    } else {
+        current
+    }
+}
 
 pub struct TextureGroup {
     pub color: glium::texture::Texture2d,
     pub normal: glium::texture::Texture2d,
     pub position: glium::texture::Texture2d,
+    // Material kind, roughness, emission and transparency of the visible
+    // voxel; see `world::Material`.
+    pub material: glium::texture::Texture2d,
     pub depth: glium::framebuffer::DepthRenderBuffer,
 }
 
 impl TextureGroup {
-    fn new(disp: &glium::Display, (width, height): (u32, u32)) -> anyhow::Result<Self> {
+    pub(super) fn new(disp: &dyn Display, (width, height): (u32, u32)) -> anyhow::Result<Self> {
         Ok(Self {
             color: glium::texture::Texture2d::empty_with_format(
                 disp,
@@ -135,6 +94,13 @@ impl TextureGroup {
                 width,
                 height,
             )?,
+            material: glium::texture::Texture2d::empty_with_format(
+                disp,
+                glium::texture::UncompressedFloatFormat::F16F16F16F16,
+                glium::texture::MipmapsOption::NoMipmap,
+                width,
+                height,
+            )?,
             depth: glium::framebuffer::DepthRenderBuffer::new(
                 disp,
                 glium::texture::DepthFormat::F32,
@@ -144,15 +110,16 @@ impl TextureGroup {
         })
     }
 
-    fn as_surface<'a>(
+    pub(super) fn as_surface<'a>(
         &'a self,
-        display: &glium::Display,
+        display: &dyn Display,
     ) -> Result<glium::framebuffer::MultiOutputFrameBuffer<'a>, glium::framebuffer::ValidationError>
     {
         let color_attachments = [
             ("color", &self.color),
             ("normal", &self.normal),
             ("position", &self.position),
+            ("material", &self.material),
         ];
         glium::framebuffer::MultiOutputFrameBuffer::with_depth_buffer(
             display,
@@ -172,7 +139,7 @@ impl<'provider> SurfaceProvider<'provider> for GBufferRendererProvider {
     type Output = &'provider TextureGroup;
     type Target = (Self::Surface, Self::Output);
 
-    fn new(display: &glium::Display) -> anyhow::Result<Self> {
+    fn new(display: &dyn Display) -> anyhow::Result<Self> {
         let dimensions = display.get_framebuffer_dimensions();
         Ok(Self {
             dimensions,
@@ -180,10 +147,7 @@ impl<'provider> SurfaceProvider<'provider> for GBufferRendererProvider {
         })
     }
 
-    fn get(
-        &'provider mut self,
-        display: &'provider glium::Display,
-    ) -> anyhow::Result<Self::Target> {
+    fn get(&'provider mut self, display: &'provider dyn Display) -> anyhow::Result<Self::Target> {
         let dimensions = display.get_framebuffer_dimensions();
         if self.dimensions != dimensions {
             self.buffer = TextureGroup::new(display, dimensions)?;
@@ -194,16 +158,82 @@ impl<'provider> SurfaceProvider<'provider> for GBufferRendererProvider {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeshMode {
+    /// One point per exposed face, expanded into a quad by cube.geom.
+    Points,
+    /// Coplanar faces of identical blocks merged into larger quads.
+    Greedy,
+}
+
 pub struct GBufferRenderer {
     vertex: glium::VertexBuffer<FaceInfo>,
-    program: glium::Program,
+    faces: MeshCache<Vec<FaceInfo>>,
+    face_count: usize,
+    program: ShaderProgram,
+    mode: MeshMode,
+    quads: Option<(glium::VertexBuffer<QuadVertex>, glium::IndexBuffer<u32>)>,
+    quad_meshes: MeshCache<GreedyMesh>,
+    quad_stats: MeshStats,
+    quad_program: ShaderProgram,
+}
+
+/// Corner brightness by how many of its three neighbours are solid.
+const AO_LEVELS: [f32; 4] = [1.0, 0.75, 0.55, 0.4];
+
+fn solid_at(
+    world: &world::World,
+    WorldPosition(x, y, z): WorldPosition,
+    [dx, dy, dz]: [i32; 3],
+) -> bool {
+    match (
+        x.checked_add_signed(dx),
+        y.checked_add_signed(dy),
+        z.checked_add_signed(dz),
+    ) {
+        (Some(x), Some(y), Some(z)) => world.test(WorldPosition(x, y, z)),
+        _ => false,
+    }
+}
+
+/// Per-vertex ambient occlusion: each corner darkens with the blocks on
+/// either side of it and diagonally across it, in front of the face. Two
+/// solid sides hide the corner entirely, whatever the diagonal holds.
+fn face_ao(world: &world::World, pos: WorldPosition, direction: world::Direction) -> [f32; 4] {
+    let normal = direction.offset();
+    let axis = normal.iter().position(|&n| n != 0).unwrap();
+    let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+    let mut ao = [1.0; 4];
+    for (value, corner) in ao
+        .iter_mut()
+        .zip(CORNERS[u32::from(direction) as usize].iter())
+    {
+        let step = |axis: usize| if corner[axis] == 1 { 1 } else { -1 };
+        let mut side_u = normal;
+        side_u[u] += step(u);
+        let mut side_v = normal;
+        side_v[v] += step(v);
+        let mut diagonal = side_u;
+        diagonal[v] += step(v);
+        let (side_u, side_v, diagonal) = (
+            solid_at(world, pos, side_u),
+            solid_at(world, pos, side_v),
+            solid_at(world, pos, diagonal),
+        );
+        let solid = if side_u && side_v {
+            3
+        } else {
+            side_u as usize + side_v as usize + diagonal as usize
+        };
+        *value = AO_LEVELS[solid];
+    }
+    ao
 }
 
 #[inline(always)]
 fn gen_face(
     world: &world::World,
-    mapped: &mut WriteMapping<[FaceInfo]>,
-    i: &mut usize,
+    faces: &mut Vec<FaceInfo>,
     pos: WorldPosition,
     blk: &world::SolidBlock,
     direction: world::Direction,
@@ -214,87 +244,167 @@ fn gen_face(
         }
     }
 
-    mapped.set(
-        *i,
-        FaceInfo {
-            position: pos.into(),
-            color: blk.into(),
-            face: direction.into(),
+    faces.push(FaceInfo {
+        position: pos.into(),
+        color: blk.into(),
+        face: direction.into(),
+        material: blk.material().into(),
+        ao: face_ao(world, pos, direction),
+    });
+}
+
+pub(super) fn chunk_faces(world: &world::World, chunk_pos: ChunkPosition) -> Option<Vec<FaceInfo>> {
+    let mut faces = Vec::new();
+    for (pos, blk) in world.iter_chunk(chunk_pos) {
+        for direction in world::Direction::iter() {
+            gen_face(world, &mut faces, pos, blk, direction);
+        }
+    }
+    if faces.is_empty() {
+        None
+    } else {
+        Some(faces)
+    }
+}
+
+fn draw_parameters() -> glium::DrawParameters<'static> {
+    glium::DrawParameters {
+        depth: glium::Depth {
+            test: glium::DepthTest::IfLess,
+            write: true,
+            ..Default::default()
         },
-    );
-    *i += 1;
+        backface_culling: glium::BackfaceCullingMode::CullClockwise,
+        ..Default::default()
+    }
 }
 
 impl GBufferRenderer {
-    fn build_vertex(
-        &mut self,
-        display: &glium::Display,
-        world: &world::World,
-    ) -> anyhow::Result<()> {
-        let faces = world.dims().max_faces();
-        if self.vertex.len() < faces {
-            self.vertex = glium::VertexBuffer::empty_dynamic(display, faces)?;
+    pub fn new(display: &dyn Display) -> anyhow::Result<Self> {
+        Ok(Self {
+            vertex: glium::VertexBuffer::empty_dynamic(display, MIN_FACES)?,
+            faces: MeshCache::default(),
+            face_count: 0,
+            program: shader_program!(display, "cube" with geometry)?,
+            mode: MeshMode::Points,
+            quads: None,
+            quad_meshes: MeshCache::default(),
+            quad_stats: MeshStats::default(),
+            quad_program: shader_program!(display, "greedy", "cube")?,
+        })
+    }
+
+    pub fn mode(&self) -> MeshMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: MeshMode) {
+        self.mode = mode;
+    }
+
+    pub fn stats(&self) -> MeshStats {
+        match self.mode {
+            MeshMode::Points => MeshStats {
+                faces: self.face_count,
+                quads: 0,
+            },
+            MeshMode::Greedy => self.quad_stats,
         }
-        let mut mapped = self.vertex.map_write();
-        let mut i = 0usize;
-        for (pos, blk) in world.iter() {
-            for direction in world::Direction::iter() {
-                gen_face(world, &mut mapped, &mut i, pos, blk, direction);
-            }
+    }
+
+    fn build_quads(&mut self, display: &dyn Display, world: &world::World) -> anyhow::Result<()> {
+        if !self.quad_meshes.update(world, |world, chunk_pos| {
+            Some(GreedyMesh::build(world, chunk_pos)).filter(|mesh| mesh.stats.quads > 0)
+        }) {
+            return Ok(());
         }
+        let mut mesh = GreedyMesh::default();
+        for chunk in self.quad_meshes.values() {
+            mesh.append(chunk);
+        }
+        self.quad_stats = mesh.stats;
+        self.quads = if mesh.indices.is_empty() {
+            None
+        } else {
+            Some((
+                glium::VertexBuffer::new(display, &mesh.vertices)?,
+                glium::IndexBuffer::new(
+                    display,
+                    glium::index::PrimitiveType::TrianglesList,
+                    &mesh.indices,
+                )?,
+            ))
+        };
+        Ok(())
+    }
+
+    fn build_vertex(&mut self, display: &dyn Display, world: &world::World) -> anyhow::Result<()> {
+        if !self.faces.update(world, chunk_faces) {
+            return Ok(());
+        }
+        let faces: Vec<_> = self.faces.values().flatten().copied().collect();
+        let capacity = face_capacity(self.vertex.len(), faces.len());
+        if capacity != self.vertex.len() {
+            self.vertex = glium::VertexBuffer::empty_dynamic(display, capacity)?;
+        }
+        if let Some(slice) = self.vertex.slice_mut(..faces.len()) {
+            slice.write(&faces);
+        }
+        self.face_count = faces.len();
         Ok(())
     }
 }
 
 impl<'pass> Pass<'pass, GBufferRendererProvider> for GBufferRenderer {
-    type Input = &'pass world::World;
+    type Input = (&'pass world::World, &'pass Camera);
 
-    fn with_provider<'a>(
-        display: &'a glium::Display,
+    fn with_provider(
+        display: &dyn Display,
         provider: GBufferRendererProvider,
     ) -> anyhow::Result<PassGroup<Self, GBufferRendererProvider>> {
-        Ok(PassGroup::new(
-            Self {
-                vertex: glium::VertexBuffer::new(display, &CUBES)?,
-                program: shader_program!(display, "cube" with geometry)?,
-            },
-            provider,
-        ))
+        Ok(PassGroup::new(Self::new(display)?, provider))
     }
 
     fn process<'surface>(
         &'pass mut self,
-        display: &'surface glium::Display,
+        display: &'surface dyn Display,
         surface: &'surface mut <GBufferRendererProvider as SurfaceProvider>::Surface,
-        input: Self::Input,
+        (world, camera): Self::Input,
     ) -> anyhow::Result<()> {
-        self.build_vertex(display, input)?;
+        match self.mode {
+            MeshMode::Points => {
+                self.program.reload(display);
+                self.build_vertex(display, world)?
+            }
+            MeshMode::Greedy => {
+                self.quad_program.reload(display);
+                self.build_quads(display, world)?
+            }
+        }
         surface.clear_color_and_depth((0.0, 0.0, 0.0, 1.0), 1.0);
         let aspect_ratio = {
             let dim = surface.get_dimensions();
             dim.0 as f32 / dim.1 as f32
         };
-        let uniforms = Projection::new(
-            aspect_ratio,
-            glam::vec3(8.0, 10.0, 8.0),
-            glam::vec3(20.0, 0.0, 20.0),
-        )
-        .to_uniform();
-        surface.draw(
-            self.vertex.slice(..).unwrap(),
-            &glium::index::NoIndices(glium::index::PrimitiveType::Points),
-            &self.program,
-            &uniforms,
-            &glium::DrawParameters {
-                depth: glium::Depth {
-                    test: glium::DepthTest::IfLess,
-                    write: true,
-                    ..Default::default()
-                },
-                backface_culling: glium::BackfaceCullingMode::CullClockwise,
-                ..Default::default()
-            },
-        )?;
+        let uniforms = Projection::new(aspect_ratio, camera).to_uniform();
+        match (self.mode, &self.quads) {
+            (MeshMode::Points, _) if self.face_count == 0 => (),
+            (MeshMode::Points, _) => surface.draw(
+                self.vertex.slice(..self.face_count).unwrap(),
+                glium::index::NoIndices(glium::index::PrimitiveType::Points),
+                &self.program,
+                &uniforms,
+                &draw_parameters(),
+            )?,
+            (MeshMode::Greedy, Some((vertices, indices))) => surface.draw(
+                vertices,
+                indices,
+                &self.quad_program,
+                &uniforms,
+                &draw_parameters(),
+            )?,
+            (MeshMode::Greedy, None) => (),
+        }
         Ok(())
     }
 }
@@ -0,0 +1,317 @@
+use std::collections::HashMap;
+
+use glium::implement_vertex;
+
+use crate::world::{ChunkPosition, Direction, SolidBlock, World, WorldPosition, CHUNK_SIZE};
+
+#[derive(Copy, Clone)]
+pub struct QuadVertex {
+    position: [f32; 3],
+    color: [f32; 3],
+    material: [f32; 4],
+}
+
+implement_vertex!(QuadVertex, position, color, material);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MeshStats {
+    /// Exposed block faces, i.e. what the point-per-face path draws.
+    pub faces: usize,
+    /// Quads left after greedy merging.
+    pub quads: usize,
+}
+
+// Unit face corners in triangle strip order, matching `faces` in cube.geom.
+pub(super) const CORNERS: [[[u32; 3]; 4]; 6] = [
+    // North
+    [[1, 0, 0], [0, 0, 0], [1, 1, 0], [0, 1, 0]],
+    // South
+    [[0, 0, 1], [1, 0, 1], [0, 1, 1], [1, 1, 1]],
+    // East
+    [[1, 0, 1], [1, 0, 0], [1, 1, 1], [1, 1, 0]],
+    // West
+    [[0, 0, 0], [0, 0, 1], [0, 1, 0], [0, 1, 1]],
+    // Up
+    [[0, 1, 1], [1, 1, 1], [0, 1, 0], [1, 1, 0]],
+    // Down
+    [[0, 0, 0], [1, 0, 0], [0, 0, 1], [1, 0, 1]],
+];
+
+/// Returns the normal axis of faces pointing in `direction` and the two
+/// axes spanning their plane.
+fn axes(direction: Direction) -> (usize, usize, usize) {
+    let normal = match direction {
+        Direction::East | Direction::West => 0,
+        Direction::Up | Direction::Down => 1,
+        Direction::North | Direction::South => 2,
+    };
+    (normal, (normal + 1) % 3, (normal + 2) % 3)
+}
+
+#[derive(Default)]
+pub struct GreedyMesh {
+    pub vertices: Vec<QuadVertex>,
+    pub indices: Vec<u32>,
+    pub stats: MeshStats,
+}
+
+impl GreedyMesh {
+    /// Collects the exposed faces of every block in `chunk_pos` into one
+    /// mask per direction and slice, then merges runs of identical blocks
+    /// in each mask into rectangles.
+    pub fn build(world: &World, chunk_pos: ChunkPosition) -> Self {
+        let dims = world.dims();
+        let WorldPosition(ox, oy, oz) = chunk_pos.origin();
+        let origin = [ox, oy, oz];
+        let mut slices = HashMap::<(u32, u32), Vec<Option<SolidBlock>>>::new();
+        let mut res = Self::default();
+        for (pos, blk) in world.iter_chunk(chunk_pos) {
+            for direction in Direction::iter() {
+                if let Some(target_pos) = direction.apply(dims, pos) {
+                    if world.test(target_pos) {
+                        continue;
+                    }
+                }
+                let (normal, u, v) = axes(direction);
+                let local = [pos.0 - origin[0], pos.1 - origin[1], pos.2 - origin[2]];
+                let mask = slices
+                    .entry((direction.into(), local[normal]))
+                    .or_insert_with(|| vec![None; (CHUNK_SIZE * CHUNK_SIZE) as usize]);
+                mask[(local[u] + local[v] * CHUNK_SIZE) as usize] = Some(*blk);
+                res.stats.faces += 1;
+            }
+        }
+
+        let mut keys: Vec<_> = slices.keys().copied().collect();
+        keys.sort_unstable();
+        for (direction, slice) in keys {
+            if let Some(mask) = slices.get_mut(&(direction, slice)) {
+                res.merge(direction.into(), origin, slice, mask);
+            }
+        }
+        res
+    }
+
+    pub fn append(&mut self, other: &GreedyMesh) {
+        let base = self.vertices.len() as u32;
+        self.vertices.extend_from_slice(&other.vertices);
+        self.indices
+            .extend(other.indices.iter().map(|index| base + index));
+        self.stats.faces += other.stats.faces;
+        self.stats.quads += other.stats.quads;
+    }
+
+    fn merge(
+        &mut self,
+        direction: Direction,
+        origin: [u32; 3],
+        slice: u32,
+        mask: &mut [Option<SolidBlock>],
+    ) {
+        let (width, height) = (CHUNK_SIZE as usize, CHUNK_SIZE as usize);
+        for j in 0..height {
+            let mut i = 0;
+            while i < width {
+                let blk = match mask[i + j * width] {
+                    Some(blk) => blk,
+                    None => {
+                        i += 1;
+                        continue;
+                    }
+                };
+                let mut w = 1;
+                while i + w < width && mask[i + w + j * width] == Some(blk) {
+                    w += 1;
+                }
+                let mut h = 1;
+                while j + h < height
+                    && mask[i + (j + h) * width..i + w + (j + h) * width]
+                        .iter()
+                        .all(|cell| *cell == Some(blk))
+                {
+                    h += 1;
+                }
+                for row in j..j + h {
+                    mask[i + row * width..i + w + row * width].fill(None);
+                }
+                self.push_quad(
+                    direction,
+                    origin,
+                    slice,
+                    [i as u32, j as u32],
+                    [w as u32, h as u32],
+                    &blk,
+                );
+                i += w;
+            }
+        }
+    }
+
+    fn push_quad(
+        &mut self,
+        direction: Direction,
+        chunk_origin: [u32; 3],
+        slice: u32,
+        [i, j]: [u32; 2],
+        [w, h]: [u32; 2],
+        blk: &SolidBlock,
+    ) {
+        let (normal, u, v) = axes(direction);
+        let mut origin = chunk_origin;
+        origin[normal] += slice;
+        origin[u] += i;
+        origin[v] += j;
+        let mut size = [1; 3];
+        size[u] = w;
+        size[v] = h;
+
+        let base = self.vertices.len() as u32;
+        for corner in &CORNERS[u32::from(direction) as usize] {
+            self.vertices.push(QuadVertex {
+                position: [0, 1, 2].map(|a| (origin[a] + corner[a] * size[a]) as f32),
+                color: blk.into(),
+                material: blk.material().into(),
+            });
+        }
+        self.indices
+            .extend([0, 1, 2, 2, 1, 3].iter().map(|k| base + k));
+        self.stats.quads += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeSet, HashMap};
+
+    use super::super::gbuffer_pass::chunk_faces;
+    use super::*;
+
+    // A unit face as the points path draws it: block, direction, color and
+    // material, with the floats compared bit for bit.
+    type UnitFace = ([u32; 3], u32, [u32; 3], [u32; 4]);
+
+    fn bits<const N: usize>(values: [f32; N]) -> [u32; N] {
+        values.map(f32::to_bits)
+    }
+
+    fn cross(a: [i64; 3], b: [i64; 3]) -> [i64; 3] {
+        [
+            a[1] * b[2] - a[2] * b[1],
+            a[2] * b[0] - a[0] * b[2],
+            a[0] * b[1] - a[1] * b[0],
+        ]
+    }
+
+    /// The direction a quad faces, from its winding, which follows
+    /// `CORNERS` whatever the quad's size.
+    fn quad_direction(corners: [[i64; 3]; 4]) -> Direction {
+        let sign = |corners: [[i64; 3]; 4]| {
+            let edge = |k: usize| [0, 1, 2].map(|a| corners[k][a] - corners[0][a]);
+            cross(edge(1), edge(2)).map(i64::signum)
+        };
+        let normal = sign(corners);
+        Direction::iter()
+            .find(|&direction| {
+                let unit = CORNERS[u32::from(direction) as usize].map(|c| c.map(i64::from));
+                sign(unit) == normal
+            })
+            .unwrap()
+    }
+
+    /// Splits every quad back into the unit faces it covers.
+    fn unit_faces(mesh: &GreedyMesh) -> Vec<UnitFace> {
+        assert_eq!(mesh.vertices.len(), mesh.stats.quads * 4);
+        assert_eq!(mesh.indices.len(), mesh.stats.quads * 6);
+        let mut res = Vec::new();
+        for quad in mesh.vertices.chunks(4) {
+            let corners = [0, 1, 2, 3].map(|k| quad[k].position.map(|v| v as i64));
+            assert!(quad.iter().all(|vertex| {
+                bits(vertex.color) == bits(quad[0].color)
+                    && bits(vertex.material) == bits(quad[0].material)
+            }));
+            let direction = quad_direction(corners);
+            let (normal, u, v) = axes(direction);
+            let offset = direction.offset();
+            let min = [0, 1, 2].map(|a| corners.iter().map(|c| c[a]).min().unwrap());
+            let max = [0, 1, 2].map(|a| corners.iter().map(|c| c[a]).max().unwrap());
+            assert_eq!(min[normal], max[normal]);
+            for j in min[v]..max[v] {
+                for i in min[u]..max[u] {
+                    let mut pos = [0; 3];
+                    pos[normal] = min[normal] - offset[normal].max(0) as i64;
+                    pos[u] = i;
+                    pos[v] = j;
+                    res.push((
+                        pos.map(|p| p as u32),
+                        direction.into(),
+                        bits(quad[0].color),
+                        bits(quad[0].material),
+                    ));
+                }
+            }
+        }
+        res
+    }
+
+    #[test]
+    fn quads_cover_exactly_the_point_faces() {
+        let assets: [(&str, &[u8]); 4] = [
+            ("doom", include_bytes!("../../assets/doom.vox")),
+            ("room", include_bytes!("../../assets/room.vox")),
+            ("teapot", include_bytes!("../../assets/teapot.vox")),
+            ("test", include_bytes!("../../assets/test.vox")),
+        ];
+        for (name, data) in assets {
+            let world = World::from_vox(data).unwrap();
+            let chunks: BTreeSet<_> = world
+                .iter()
+                .map(|(pos, _)| ChunkPosition::split(pos).0)
+                .collect();
+            let mut total = GreedyMesh::default();
+            for chunk_pos in chunks {
+                let mesh = GreedyMesh::build(&world, chunk_pos);
+                let faces = chunk_faces(&world, chunk_pos).unwrap_or_default();
+                let mut expected: Vec<UnitFace> = faces
+                    .iter()
+                    .map(|face| {
+                        (
+                            face.position.map(|v| v as u32),
+                            face.face,
+                            bits(face.color),
+                            bits(face.material),
+                        )
+                    })
+                    .collect();
+                expected.sort_unstable();
+                let mut covered = unit_faces(&mesh);
+                covered.sort_unstable();
+                assert_eq!(mesh.stats.faces, faces.len(), "{} {:?}", name, chunk_pos);
+                assert_eq!(covered, expected, "{} {:?}", name, chunk_pos);
+                total.append(&mesh);
+            }
+            // Merging has to win something on real models.
+            assert!(total.stats.quads < total.stats.faces, "{}", name);
+            assert_eq!(unit_faces(&total).len(), total.stats.faces, "{}", name);
+        }
+    }
+
+    #[test]
+    fn a_flat_slab_merges_into_one_quad_per_side() {
+        let mut world = World::new((8, 1, 8));
+        for z in 0..8 {
+            for x in 0..8 {
+                world.set(WorldPosition(x, 0, z), crate::world::Block::solid(9, 9, 9));
+            }
+        }
+        let mesh = GreedyMesh::build(&world, ChunkPosition(0, 0, 0));
+        assert_eq!(mesh.stats.faces, 64 * 2 + 8 * 4);
+        assert_eq!(mesh.stats.quads, 6);
+        let mut areas: HashMap<u32, usize> = HashMap::new();
+        for (_, direction, _, _) in unit_faces(&mesh) {
+            *areas.entry(direction).or_default() += 1;
+        }
+        let up = u32::from(Direction::Up);
+        let north = u32::from(Direction::North);
+        assert_eq!((areas[&up], areas[&north]), (64, 8));
+    }
+}
@@ -0,0 +1,55 @@
+use std::collections::HashMap;
+
+use crate::world::{ChunkPosition, World};
+
+/// Per-chunk meshes of one world, rebuilt only for chunks the world
+/// reports as changed since the last update.
+pub struct MeshCache<T> {
+    version: Option<(u64, u64)>,
+    chunks: HashMap<ChunkPosition, T>,
+}
+
+impl<T> Default for MeshCache<T> {
+    fn default() -> Self {
+        Self {
+            version: None,
+            chunks: HashMap::new(),
+        }
+    }
+}
+
+impl<T> MeshCache<T> {
+    /// Re-meshes dirty chunks with `build`, which returns `None` for chunks
+    /// with nothing to draw. Returns `false` if the cache was already up to
+    /// date, in which case `build` is never called.
+    pub fn update<F>(&mut self, world: &World, mut build: F) -> bool
+    where
+        F: FnMut(&World, ChunkPosition) -> Option<T>,
+    {
+        let since = match self.version {
+            Some((id, generation)) if id == world.id() => {
+                if generation == world.generation() {
+                    return false;
+                }
+                generation
+            }
+            _ => {
+                self.chunks.clear();
+                0
+            }
+        };
+        let dirty: Vec<_> = world.changes_since(since).collect();
+        for chunk_pos in dirty {
+            match build(world, chunk_pos) {
+                Some(mesh) => self.chunks.insert(chunk_pos, mesh),
+                None => self.chunks.remove(&chunk_pos),
+            };
+        }
+        self.version = Some((world.id(), world.generation()));
+        true
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.chunks.values()
+    }
+}
@@ -1,10 +1,39 @@
 pub mod blur_pass;
+pub mod composite_pass;
+pub mod debug_pass;
+pub mod description;
+pub mod dyn_pass;
 pub mod gbuffer_pass;
+pub mod greedy_mesh;
+pub mod mesh_cache;
 pub mod outline_pass;
-pub mod debug_pass;
 pub mod postprocess;
+pub mod preset;
+pub mod render_graph;
+pub mod shader;
+pub mod ssao_pass;
 pub mod strengthen_pass;
 
+use glium::backend::Facade;
+
+/// Whatever the passes render with: a window, or an offscreen context for
+/// headless rendering.
+pub trait Display: Facade {
+    fn get_framebuffer_dimensions(&self) -> (u32, u32);
+
+    fn draw(&self) -> glium::Frame;
+}
+
+impl Display for glium::Display {
+    fn get_framebuffer_dimensions(&self) -> (u32, u32) {
+        self.get_context().get_framebuffer_dimensions()
+    }
+
+    fn draw(&self) -> glium::Frame {
+        glium::Display::draw(self)
+    }
+}
+
 pub trait SurfaceInstance<Surface: glium::Surface, Output: Sized> {
     fn surface(&mut self) -> &mut Surface;
 
@@ -31,10 +60,9 @@ where
     type Output: Sized;
     type Target: SurfaceInstance<Self::Surface, Self::Output>;
 
-    fn new(display: &glium::Display) -> anyhow::Result<Self>;
+    fn new(display: &dyn Display) -> anyhow::Result<Self>;
 
-    fn get(&'provider mut self, display: &'provider glium::Display)
-        -> anyhow::Result<Self::Target>;
+    fn get(&'provider mut self, display: &'provider dyn Display) -> anyhow::Result<Self::Target>;
 }
 
 pub trait Pass<'pass, Provider>
@@ -44,14 +72,14 @@ where
 {
     type Input;
 
-    fn with_provider<'display>(
-        display: &'display glium::Display,
+    fn with_provider(
+        display: &dyn Display,
         provider: Provider,
     ) -> anyhow::Result<PassGroup<Self, Provider>>;
 
     fn process<'surface>(
         &'pass mut self,
-        display: &'surface glium::Display,
+        display: &'surface dyn Display,
         surface: &'surface mut <Provider as SurfaceProvider<'pass>>::Surface,
         input: Self::Input,
     ) -> anyhow::Result<()>;
@@ -62,7 +90,7 @@ pub trait ProcessPass<'pass, Input> {
 
     fn process(
         &'pass mut self,
-        display: &'pass glium::Display,
+        display: &'pass dyn Display,
         input: Input,
     ) -> anyhow::Result<Self::Output>;
 }
@@ -81,11 +109,21 @@ where
         Self { pass, provider }
     }
 
-    pub fn create(display: &glium::Display) -> anyhow::Result<Self> {
+    pub fn create(display: &dyn Display) -> anyhow::Result<Self> {
         ThisPass::with_provider(display, Provider::new(display)?)
     }
 }
 
+impl<ThisPass, Provider> PassGroup<ThisPass, Provider> {
+    pub fn pass_mut(&mut self) -> &mut ThisPass {
+        &mut self.pass
+    }
+
+    pub fn into_pass(self) -> ThisPass {
+        self.pass
+    }
+}
+
 impl<'pass, ThisPass, Provider> ProcessPass<'pass, ThisPass::Input>
     for PassGroup<ThisPass, Provider>
 where
@@ -96,7 +134,7 @@ where
 
     fn process(
         &'pass mut self,
-        display: &'pass glium::Display,
+        display: &'pass dyn Display,
         input: ThisPass::Input,
     ) -> anyhow::Result<Self::Output> {
         let mut out = self.provider.get(display)?;
@@ -107,6 +145,16 @@ where
 
 pub struct PassChain<A, B>(A, B);
 
+impl<A, B> PassChain<A, B> {
+    pub fn first_mut(&mut self) -> &mut A {
+        &mut self.0
+    }
+
+    pub fn second_mut(&mut self) -> &mut B {
+        &mut self.1
+    }
+}
+
 impl<'pass, I, A, B> ProcessPass<'pass, I> for PassChain<A, B>
 where
     A: ProcessPass<'pass, I>,
@@ -116,13 +164,89 @@ where
 
     fn process(
         &'pass mut self,
-        display: &'pass glium::Display,
+        display: &'pass dyn Display,
         input: I,
     ) -> anyhow::Result<Self::Output> {
         self.1.process(display, self.0.process(display, input)?)
     }
 }
 
+/// Sends the same input to two passes and joins their outputs into a
+/// tuple, e.g. to feed one G-buffer to both `OutlinePass` and `DebugPass`.
+pub struct PassFork<A, B>(A, B);
+
+impl<A, B> PassFork<A, B> {
+    pub fn first_mut(&mut self) -> &mut A {
+        &mut self.0
+    }
+}
+
+impl<'pass, I, A, B> ProcessPass<'pass, I> for PassFork<A, B>
+where
+    I: Copy,
+    A: ProcessPass<'pass, I>,
+    B: ProcessPass<'pass, I>,
+{
+    type Output = (A::Output, B::Output);
+
+    fn process(
+        &'pass mut self,
+        display: &'pass dyn Display,
+        input: I,
+    ) -> anyhow::Result<Self::Output> {
+        Ok((
+            self.0.process(display, input)?,
+            self.1.process(display, input)?,
+        ))
+    }
+}
+
+/// Wraps a pass whose output has the type of its input so it can be
+/// switched off at runtime, handing its input straight through instead.
+pub struct PassBypass<A> {
+    pass: A,
+    enabled: bool,
+}
+
+impl<A> PassBypass<A> {
+    pub fn new(pass: A) -> Self {
+        Self {
+            pass,
+            enabled: true,
+        }
+    }
+
+    /// Switches the pass on or off, returning whether it is now on.
+    pub fn toggle(&mut self) -> bool {
+        self.enabled = !self.enabled;
+        self.enabled
+    }
+
+    pub fn pass_mut(&mut self) -> &mut A {
+        &mut self.pass
+    }
+}
+
+impl<'pass, I, A> ProcessPass<'pass, I> for PassBypass<A>
+where
+    A: ProcessPass<'pass, I, Output = I>,
+{
+    type Output = I;
+
+    fn process(
+        &'pass mut self,
+        display: &'pass dyn Display,
+        input: I,
+    ) -> anyhow::Result<Self::Output> {
+        if self.enabled {
+            self.pass.process(display, input)
+        } else {
+            Ok(input)
+        }
+    }
+}
+
+#[allow(dead_code)]
 pub struct PassWith<A, T>(A, T)
 where
     T: Clone;
@@ -136,7 +260,7 @@ where
 
     fn process(
         &'pass mut self,
-        display: &'pass glium::Display,
+        display: &'pass dyn Display,
         input: I,
     ) -> anyhow::Result<Self::Output> {
         self.0.process(display, (input, self.1.clone()))
@@ -165,6 +289,30 @@ where
     }
 }
 
+pub trait ForkablePass<'pass, I, Rhs>
+where
+    Self: ProcessPass<'pass, I>,
+    Rhs: ProcessPass<'pass, I>,
+{
+    type Target: ProcessPass<'pass, I>;
+
+    fn fork(self, rhs: Rhs) -> Self::Target;
+}
+
+impl<'pass, I, T, Rhs> ForkablePass<'pass, I, Rhs> for T
+where
+    I: Copy,
+    T: ProcessPass<'pass, I>,
+    Rhs: ProcessPass<'pass, I>,
+{
+    type Target = PassFork<Self, Rhs>;
+
+    fn fork(self, rhs: Rhs) -> Self::Target {
+        PassFork(self, rhs)
+    }
+}
+
+#[allow(dead_code)]
 pub trait WithPass<'pass, I, T>
 where
     Self: ProcessPass<'pass, (I, T)>,
@@ -216,15 +364,55 @@ impl<'provider> SurfaceProvider<'provider> for DisplaySurfaceProvider {
     type Output = FrameWrapper;
     type Target = FrameWrapper;
 
-    fn new(_display: &glium::Display) -> anyhow::Result<Self> {
+    fn new(_display: &dyn Display) -> anyhow::Result<Self> {
         Ok(Self)
     }
 
-    fn get(
-        &'provider mut self,
-        display: &'provider glium::Display,
-    ) -> anyhow::Result<Self::Target> {
+    fn get(&'provider mut self, display: &'provider dyn Display) -> anyhow::Result<Self::Target> {
         let frame = display.draw();
         Ok(FrameWrapper::new(frame))
     }
 }
+
+/// Renders into an RGBA8 texture instead of the default framebuffer, so
+/// the result can be read back.
+pub struct OffscreenSurfaceProvider {
+    dimensions: (u32, u32),
+    texture: glium::texture::Texture2d,
+}
+
+impl OffscreenSurfaceProvider {
+    fn texture(display: &dyn Display) -> anyhow::Result<glium::texture::Texture2d> {
+        let (width, height) = display.get_framebuffer_dimensions();
+        Ok(glium::texture::Texture2d::empty_with_format(
+            display,
+            glium::texture::UncompressedFloatFormat::U8U8U8U8,
+            glium::texture::MipmapsOption::NoMipmap,
+            width,
+            height,
+        )?)
+    }
+}
+
+impl<'provider> SurfaceProvider<'provider> for OffscreenSurfaceProvider {
+    type Surface = glium::framebuffer::SimpleFrameBuffer<'provider>;
+    type Output = &'provider glium::texture::Texture2d;
+    type Target = (Self::Surface, Self::Output);
+
+    fn new(display: &dyn Display) -> anyhow::Result<Self> {
+        Ok(Self {
+            dimensions: display.get_framebuffer_dimensions(),
+            texture: Self::texture(display)?,
+        })
+    }
+
+    fn get(&'provider mut self, display: &'provider dyn Display) -> anyhow::Result<Self::Target> {
+        let dimensions = display.get_framebuffer_dimensions();
+        if self.dimensions != dimensions {
+            self.texture = Self::texture(display)?;
+            self.dimensions = dimensions;
+        }
+        let surface = glium::framebuffer::SimpleFrameBuffer::new(display, &self.texture)?;
+        Ok((surface, &self.texture))
+    }
+}
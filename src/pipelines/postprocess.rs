@@ -1,6 +1,6 @@
 use glium::{implement_vertex, uniform, Surface};
 
-use super::{Pass, PassGroup, SurfaceProvider};
+use super::{shader::ShaderProgram, Display, Pass, PassGroup, SurfaceProvider};
 
 #[derive(Copy, Clone)]
 pub struct PostProcessVertex {
@@ -20,7 +20,7 @@ impl PostProcessVertex {
     }
 
     pub fn get_buffer(
-        display: &glium::Display,
+        display: &dyn Display,
     ) -> Result<glium::VertexBuffer<PostProcessVertex>, glium::vertex::BufferCreationError> {
         glium::VertexBuffer::new(display, PostProcessVertex::get())
     }
@@ -29,10 +29,14 @@ impl PostProcessVertex {
 struct TextureGroup(glium::texture::Texture2d);
 
 impl TextureGroup {
-    fn new(display: &glium::Display, (width, height): (u32, u32)) -> anyhow::Result<Self> {
+    fn new(
+        display: &dyn Display,
+        format: glium::texture::UncompressedFloatFormat,
+        (width, height): (u32, u32),
+    ) -> anyhow::Result<Self> {
         Ok(Self(glium::texture::Texture2d::empty_with_format(
             display,
-            glium::texture::UncompressedFloatFormat::F16F16F16F16,
+            format,
             glium::texture::MipmapsOption::NoMipmap,
             width,
             height,
@@ -41,7 +45,7 @@ impl TextureGroup {
 
     fn as_surface<'a>(
         &'a self,
-        display: &glium::Display,
+        display: &dyn Display,
     ) -> Result<glium::framebuffer::SimpleFrameBuffer<'a>, glium::framebuffer::ValidationError>
     {
         glium::framebuffer::SimpleFrameBuffer::new(display, &self.0)
@@ -50,29 +54,41 @@ impl TextureGroup {
 
 pub struct PostProcessProvider {
     dimensions: (u32, u32),
+    format: glium::texture::UncompressedFloatFormat,
     group: TextureGroup,
 }
 
+impl PostProcessProvider {
+    /// Renders into textures of `format` instead of the default RGBA16F.
+    pub fn with_format(
+        display: &dyn Display,
+        format: glium::texture::UncompressedFloatFormat,
+    ) -> anyhow::Result<Self> {
+        let dimensions = display.get_framebuffer_dimensions();
+        Ok(Self {
+            dimensions,
+            format,
+            group: TextureGroup::new(display, format, dimensions)?,
+        })
+    }
+}
+
 impl<'provider> SurfaceProvider<'provider> for PostProcessProvider {
     type Surface = glium::framebuffer::SimpleFrameBuffer<'provider>;
     type Output = &'provider glium::texture::Texture2d;
     type Target = (Self::Surface, Self::Output);
 
-    fn new(display: &glium::Display) -> anyhow::Result<Self> {
-        let dimensions = display.get_framebuffer_dimensions();
-        Ok(Self {
-            dimensions,
-            group: TextureGroup::new(display, dimensions)?,
-        })
+    fn new(display: &dyn Display) -> anyhow::Result<Self> {
+        Self::with_format(
+            display,
+            glium::texture::UncompressedFloatFormat::F16F16F16F16,
+        )
     }
 
-    fn get(
-        &'provider mut self,
-        display: &'provider glium::Display,
-    ) -> anyhow::Result<Self::Target> {
+    fn get(&'provider mut self, display: &'provider dyn Display) -> anyhow::Result<Self::Target> {
         let dimensions = display.get_framebuffer_dimensions();
         if self.dimensions != dimensions {
-            self.group = TextureGroup::new(display, dimensions)?;
+            self.group = TextureGroup::new(display, self.format, dimensions)?;
             self.dimensions = dimensions;
         }
         let surface = self.group.as_surface(display)?;
@@ -80,20 +96,121 @@ impl<'provider> SurfaceProvider<'provider> for PostProcessProvider {
     }
 }
 
-pub trait SimplePostProcessPipeline {
+/// Two textures that passes take turns drawing into, so an effect run
+/// several times only needs two textures however many passes it makes.
+pub struct PingPongProvider {
+    dimensions: (u32, u32),
+    format: glium::texture::UncompressedFloatFormat,
+    groups: [TextureGroup; 2],
+    current: usize,
+}
+
+impl PingPongProvider {
+    /// Switches to the texture not drawn last, reallocating both if the
+    /// framebuffer was resized.
+    fn advance(&mut self, display: &dyn Display) -> anyhow::Result<()> {
+        let dimensions = display.get_framebuffer_dimensions();
+        if self.dimensions != dimensions {
+            self.groups = [
+                TextureGroup::new(display, self.format, dimensions)?,
+                TextureGroup::new(display, self.format, dimensions)?,
+            ];
+            self.dimensions = dimensions;
+        }
+        self.current = 1 - self.current;
+        Ok(())
+    }
+
+    /// Targets the texture not drawn last, returning a surface over it and
+    /// the one drawn last to read from.
+    pub fn flip<'a>(
+        &'a mut self,
+        display: &dyn Display,
+    ) -> anyhow::Result<(
+        glium::framebuffer::SimpleFrameBuffer<'a>,
+        &'a glium::texture::Texture2d,
+    )> {
+        self.advance(display)?;
+        Ok((
+            self.groups[self.current].as_surface(display)?,
+            &self.groups[1 - self.current].0,
+        ))
+    }
+
+    /// The texture drawn last.
+    pub fn last(&self) -> &glium::texture::Texture2d {
+        &self.groups[self.current].0
+    }
+}
+
+impl<'provider> SurfaceProvider<'provider> for PingPongProvider {
+    type Surface = glium::framebuffer::SimpleFrameBuffer<'provider>;
+    type Output = &'provider glium::texture::Texture2d;
+    type Target = (Self::Surface, Self::Output);
+
+    fn new(display: &dyn Display) -> anyhow::Result<Self> {
+        let format = glium::texture::UncompressedFloatFormat::F16F16F16F16;
+        let dimensions = display.get_framebuffer_dimensions();
+        Ok(Self {
+            dimensions,
+            format,
+            groups: [
+                TextureGroup::new(display, format, dimensions)?,
+                TextureGroup::new(display, format, dimensions)?,
+            ],
+            current: 0,
+        })
+    }
+
+    fn get(&'provider mut self, display: &'provider dyn Display) -> anyhow::Result<Self::Target> {
+        self.advance(display)?;
+        let group = &self.groups[self.current];
+        Ok((group.as_surface(display)?, &group.0))
+    }
+}
+
+/// A fullscreen effect whose parameters live in the implementing type and
+/// reach the shader as a uniform block.
+pub trait SimplePostProcessPipeline: Default {
     type Block: glium::uniforms::UniformBlock + glium::buffer::Content + Copy;
 
     fn load_shader(
-        display: &glium::Display,
-    ) -> Result<glium::Program, glium::program::ProgramCreationError>;
+        display: &dyn Display,
+    ) -> Result<ShaderProgram, glium::program::ProgramCreationError>;
 
-    fn get_block() -> Self::Block;
+    fn get_block(&self) -> Self::Block;
 }
 
 pub struct PostProcessPipeline<T: SimplePostProcessPipeline> {
-    _impl: std::marker::PhantomData<T>,
+    params: T,
     vertex: glium::VertexBuffer<PostProcessVertex>,
-    program: glium::Program,
+    program: ShaderProgram,
+    block: glium::uniforms::UniformBuffer<T::Block>,
+    dirty: bool,
+}
+
+impl<T: SimplePostProcessPipeline> PostProcessPipeline<T> {
+    pub fn new(display: &dyn Display) -> anyhow::Result<Self> {
+        let params = T::default();
+        Ok(Self {
+            block: glium::uniforms::UniformBuffer::new(display, params.get_block())?,
+            params,
+            vertex: PostProcessVertex::get_buffer(display)?,
+            program: T::load_shader(display)?,
+            dirty: false,
+        })
+    }
+
+    pub fn params(&self) -> &T {
+        &self.params
+    }
+
+    /// The parameters of this instance; changes are uploaded before the
+    /// next frame.
+    pub fn params_mut(&mut self) -> &mut T {
+        self.dirty = true;
+        &mut self.params
+    }
 }
 
 impl<'pass, T, Provider> Pass<'pass, Provider> for PostProcessPipeline<T>
@@ -103,35 +220,31 @@ where
 {
     type Input = &'pass glium::texture::Texture2d;
 
-    fn with_provider<'display>(
-        display: &'display glium::Display,
+    fn with_provider(
+        display: &dyn Display,
         provider: Provider,
     ) -> anyhow::Result<PassGroup<Self, Provider>> {
-        Ok(PassGroup::new(
-            Self {
-                _impl: std::marker::PhantomData,
-                vertex: PostProcessVertex::get_buffer(display)?,
-                program: T::load_shader(display)?,
-            },
-            provider,
-        ))
+        Ok(PassGroup::new(Self::new(display)?, provider))
     }
 
     fn process<'surface>(
         &'pass mut self,
-        display: &'surface glium::Display,
+        display: &'surface dyn Display,
         surface: &'surface mut <Provider as SurfaceProvider<'pass>>::Surface,
         input: Self::Input,
     ) -> anyhow::Result<()> {
-        let block = T::get_block();
-        let block = glium::uniforms::UniformBuffer::new(display, block)?;
+        self.program.reload(display);
+        if self.dirty {
+            self.block.write(&self.params.get_block());
+            self.dirty = false;
+        }
         let sample = input
             .sampled()
             .minify_filter(glium::uniforms::MinifySamplerFilter::Linear)
             .wrap_function(glium::uniforms::SamplerWrapFunction::MirrorClamp);
         let uniforms = uniform! {
             color_sample: sample,
-            block: &block,
+            block: &self.block,
         };
 
         surface.draw(
@@ -0,0 +1,218 @@
+use std::{fmt, str::FromStr};
+
+use crate::{camera::Camera, world::World};
+
+use super::{
+    blur_pass::GaussianBlurPass,
+    composite_pass::CompositePass,
+    debug_pass::DebugPass,
+    description::{DescribedPipeline, PipelineDescription},
+    gbuffer_pass::{GBufferRenderer, GBufferRendererProvider},
+    outline_pass::OutlinePass,
+    postprocess::{PostProcessPipeline, PostProcessProvider},
+    ssao_pass::SsaoPass,
+    strengthen_pass::StrengthenPass,
+    ChainablePass, Display, ForkablePass, PassBypass, PassChain, PassFork, PassGroup, ProcessPass,
+    SurfaceProvider,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PipelinePreset {
+    /// Ambient occlusion, then outlines with the strengthen glow; the
+    /// default look.
+    Outline,
+    /// Edge detection terms from the G-buffer as colors.
+    Debug,
+    /// Outlines under a Gaussian blur.
+    Blur,
+    /// Outlines with a glow that can be switched off on the left, debug
+    /// terms on the right.
+    Compare,
+}
+
+impl FromStr for PipelinePreset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "outline" => Ok(Self::Outline),
+            "debug" => Ok(Self::Debug),
+            "blur" => Ok(Self::Blur),
+            "compare" => Ok(Self::Compare),
+            _ => Err(format!(
+                "unknown pipeline `{}`, expected outline, debug, blur or compare",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for PipelinePreset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Outline => "outline",
+            Self::Debug => "debug",
+            Self::Blur => "blur",
+            Self::Compare => "compare",
+        })
+    }
+}
+
+type GBufferGroup = PassGroup<GBufferRenderer, GBufferRendererProvider>;
+type OutlineGroup = PassGroup<OutlinePass, PostProcessProvider>;
+type StrengthenGroup = PassGroup<PostProcessPipeline<StrengthenPass>, PostProcessProvider>;
+type DebugGroup = PassGroup<DebugPass, PostProcessProvider>;
+
+pub type OutlinePipeline<Provider> = PassChain<
+    PassChain<PassChain<GBufferGroup, PassBypass<SsaoPass>>, OutlineGroup>,
+    PassGroup<PostProcessPipeline<StrengthenPass>, Provider>,
+>;
+pub type DebugPipeline<Provider> = PassChain<GBufferGroup, PassGroup<DebugPass, Provider>>;
+pub type BlurPipeline<Provider> =
+    PassChain<PassChain<GBufferGroup, OutlineGroup>, PassGroup<GaussianBlurPass, Provider>>;
+pub type ComparePipeline<Provider> = PassChain<
+    PassChain<
+        GBufferGroup,
+        PassFork<PassChain<OutlineGroup, PassBypass<StrengthenGroup>>, DebugGroup>,
+    >,
+    PassGroup<CompositePass, Provider>,
+>;
+
+/// One of the preset pass chains or a described one, ending in `Provider`
+/// so the same pipeline can draw to a window or offscreen.
+pub enum PresetPipeline<Provider>
+where
+    Provider: for<'a> SurfaceProvider<'a>,
+{
+    Outline(Box<OutlinePipeline<Provider>>),
+    Debug(Box<DebugPipeline<Provider>>),
+    Blur(Box<BlurPipeline<Provider>>),
+    Compare(Box<ComparePipeline<Provider>>),
+    Described(Box<DescribedPipeline<Provider>>),
+}
+
+impl<Provider> PresetPipeline<Provider>
+where
+    Provider: for<'a> SurfaceProvider<'a> + 'static,
+{
+    pub fn create(display: &dyn Display, preset: PipelinePreset) -> anyhow::Result<Self> {
+        let gbuffer = GBufferGroup::create(display)?;
+        Ok(match preset {
+            PipelinePreset::Outline => Self::Outline(Box::new(
+                gbuffer
+                    .chain(PassBypass::new(SsaoPass::new(display)?))
+                    .chain(OutlineGroup::create(display)?)
+                    .chain(PassGroup::create(display)?),
+            )),
+            PipelinePreset::Debug => {
+                Self::Debug(Box::new(gbuffer.chain(PassGroup::create(display)?)))
+            }
+            PipelinePreset::Blur => {
+                let mut blur = PassGroup::<GaussianBlurPass, _>::create(display)?;
+                blur.pass_mut().set_iterations(2);
+                Self::Blur(Box::new(
+                    gbuffer.chain(OutlineGroup::create(display)?).chain(blur),
+                ))
+            }
+            PipelinePreset::Compare => {
+                let outline = OutlineGroup::create(display)?
+                    .chain(PassBypass::new(StrengthenGroup::create(display)?));
+                Self::Compare(Box::new(
+                    gbuffer
+                        .chain(outline.fork(DebugGroup::create(display)?))
+                        .chain(PassGroup::create(display)?),
+                ))
+            }
+        })
+    }
+
+    pub fn describe(
+        display: &dyn Display,
+        description: &PipelineDescription,
+    ) -> anyhow::Result<Self> {
+        Ok(Self::Described(Box::new(DescribedPipeline::create(
+            display,
+            description,
+        )?)))
+    }
+
+    pub fn renderer_mut(&mut self) -> &mut GBufferRenderer {
+        match self {
+            Self::Outline(pipeline) => pipeline.first_mut().first_mut().first_mut().pass_mut(),
+            Self::Debug(pipeline) => pipeline.first_mut().pass_mut(),
+            Self::Blur(pipeline) => pipeline.first_mut().first_mut().pass_mut(),
+            Self::Compare(pipeline) => pipeline.first_mut().first_mut().pass_mut(),
+            Self::Described(pipeline) => pipeline.renderer_mut(),
+        }
+    }
+
+    /// Sets the depth range of presets that fade with distance. Described
+    /// pipelines take it from the camera themselves.
+    pub fn set_depth_range(&mut self, range: (f32, f32)) {
+        if let Self::Described(_) = self {
+            return;
+        }
+        if let Some(strengthen) = self.strengthen_mut() {
+            strengthen.params_mut().set_depth_range(range);
+        }
+    }
+
+    /// The glow pass whose strength the viewer adjusts, if there is one.
+    pub fn strengthen_mut(&mut self) -> Option<&mut PostProcessPipeline<StrengthenPass>> {
+        match self {
+            Self::Outline(pipeline) => Some(pipeline.second_mut().pass_mut()),
+            Self::Compare(pipeline) => Some(
+                pipeline
+                    .first_mut()
+                    .second_mut()
+                    .first_mut()
+                    .second_mut()
+                    .pass_mut()
+                    .pass_mut(),
+            ),
+            Self::Described(pipeline) => pipeline.strengthen_mut(),
+            Self::Debug(_) | Self::Blur(_) => None,
+        }
+    }
+
+    /// Switches the pass that can be bypassed on or off, returning whether
+    /// it is now on; `None` if the pipeline has no such pass.
+    pub fn toggle_bypass(&mut self) -> Option<bool> {
+        match self {
+            Self::Outline(pipeline) => Some(pipeline.first_mut().first_mut().second_mut().toggle()),
+            Self::Compare(pipeline) => Some(
+                pipeline
+                    .first_mut()
+                    .second_mut()
+                    .first_mut()
+                    .second_mut()
+                    .toggle(),
+            ),
+            Self::Debug(_) | Self::Blur(_) | Self::Described(_) => None,
+        }
+    }
+
+    pub fn process<'a>(
+        &'a mut self,
+        display: &'a dyn Display,
+        input: (&'a World, &'a Camera),
+    ) -> anyhow::Result<<Provider as SurfaceProvider<'a>>::Output> {
+        if let Self::Outline(pipeline) = self {
+            let (width, height) = display.get_framebuffer_dimensions();
+            let projection = input.1.projection(width as f32 / height as f32);
+            pipeline
+                .first_mut()
+                .first_mut()
+                .second_mut()
+                .pass_mut()
+                .set_projection(projection);
+        }
+        match self {
+            Self::Outline(pipeline) => pipeline.process(display, input),
+            Self::Debug(pipeline) => pipeline.process(display, input),
+            Self::Blur(pipeline) => pipeline.process(display, input),
+            Self::Compare(pipeline) => pipeline.process(display, input),
+            Self::Described(pipeline) => pipeline.process(display, input),
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use std::{
+    any::Any,
+    collections::{BTreeSet, HashMap},
+};
+
+use anyhow::{bail, Context};
+use glium::texture::Texture2d;
+
+use crate::{camera::Camera, world::World};
+
+use super::{
+    dyn_pass::{DynPass, ResourceDesc, ResourceKind, Resources, GBUFFER_ATTACHMENTS},
+    Display,
+};
+
+/// The resource a name refers to: `gbuffer.position` reads `gbuffer`.
+fn base(name: &str) -> &str {
+    name.split_once('.').map_or(name, |(base, _)| base)
+}
+
+/// Kahn's algorithm over pass indices, preferring the order the passes were
+/// added in when several are ready.
+fn execution_order(passes: &[Box<dyn DynPass>]) -> anyhow::Result<Vec<usize>> {
+    let mut producers = HashMap::new();
+    for (index, pass) in passes.iter().enumerate() {
+        if producers.insert(pass.output(), index).is_some() {
+            bail!("more than one pass writes `{}`", pass.output());
+        }
+    }
+
+    let mut dependents = vec![Vec::new(); passes.len()];
+    let mut pending = vec![0; passes.len()];
+    for (index, pass) in passes.iter().enumerate() {
+        for input in pass.inputs() {
+            let producer = match producers.get(base(input)) {
+                Some(&producer) => producer,
+                None => bail!(
+                    "`{}` reads `{}`, which no pass writes",
+                    pass.output(),
+                    input
+                ),
+            };
+            let kind = match (passes[producer].output_desc(), input.split_once('.')) {
+                (ResourceDesc::GBuffer, Some((_, part))) if GBUFFER_ATTACHMENTS.contains(&part) => {
+                    ResourceKind::Texture
+                }
+                (_, Some(_)) => bail!("`{}` doesn't name a G-buffer attachment", input),
+                (desc, None) => desc.kind(),
+            };
+            if pass.input_kind() != Some(kind) {
+                bail!("`{}` can't read `{}`, a {:?}", pass.output(), input, kind);
+            }
+            dependents[producer].push(index);
+            pending[index] += 1;
+        }
+    }
+
+    let mut ready: BTreeSet<_> = (0..passes.len()).filter(|&i| pending[i] == 0).collect();
+    let mut order = Vec::with_capacity(passes.len());
+    while let Some(index) = ready.pop_first() {
+        order.push(index);
+        for &dependent in &dependents[index] {
+            pending[dependent] -= 1;
+            if pending[dependent] == 0 {
+                ready.insert(dependent);
+            }
+        }
+    }
+    if order.len() != passes.len() {
+        bail!("the passes depend on each other in a cycle");
+    }
+    Ok(order)
+}
+
+/// Binds every output to a pool slot. A slot is reused once the last pass
+/// reading its previous resource has run, so resources whose lifetimes
+/// don't overlap alias the same texture.
+fn assign_slots(
+    passes: &[Box<dyn DynPass>],
+    output: &str,
+) -> (Vec<ResourceDesc>, HashMap<String, usize>) {
+    let mut last_read: HashMap<&str, usize> = HashMap::new();
+    for (step, pass) in passes.iter().enumerate() {
+        for input in pass.inputs() {
+            last_read.insert(base(input), step);
+        }
+    }
+    // The graph output is read after every pass.
+    last_read.insert(base(output), passes.len());
+
+    let mut slots = Vec::new();
+    let mut busy_until = Vec::new();
+    let mut bindings = HashMap::new();
+    for (step, pass) in passes.iter().enumerate() {
+        let desc = pass.output_desc();
+        let free = (0..slots.len()).find(|&slot| slots[slot] == desc && busy_until[slot] < step);
+        let slot = free.unwrap_or_else(|| {
+            slots.push(desc);
+            busy_until.push(0);
+            slots.len() - 1
+        });
+        busy_until[slot] = last_read.get(pass.output()).copied().unwrap_or(step);
+        bindings.insert(pass.output().to_string(), slot);
+    }
+    (slots, bindings)
+}
+
+/// Passes over named resources, run in dependency order. Textures come
+/// from a pool sized to the framebuffer and reallocated when it resizes.
+pub struct RenderGraph {
+    passes: Vec<Box<dyn DynPass>>,
+    output: String,
+    slots: Vec<ResourceDesc>,
+    resources: Resources,
+    dimensions: Option<(u32, u32)>,
+}
+
+impl RenderGraph {
+    /// Orders `passes` and lays out their resources; `output` names the
+    /// texture `process` returns.
+    pub fn build(mut passes: Vec<Box<dyn DynPass>>, output: &str) -> anyhow::Result<Self> {
+        let order = execution_order(&passes)?;
+        let mut unordered: Vec<_> = passes.drain(..).map(Some).collect();
+        let passes: Vec<_> = order
+            .into_iter()
+            .filter_map(|index| unordered[index].take())
+            .collect();
+        match passes.iter().find(|pass| pass.output() == base(output)) {
+            Some(pass) if pass.output_desc().kind() == ResourceKind::Texture => (),
+            Some(_) if output.contains('.') => (),
+            Some(_) => bail!("the output `{}` isn't a texture", output),
+            None => bail!("no pass writes the output `{}`", output),
+        }
+        let (slots, bindings) = assign_slots(&passes, output);
+        Ok(Self {
+            passes,
+            output: output.to_string(),
+            slots,
+            resources: Resources::new(bindings),
+            dimensions: None,
+        })
+    }
+
+    /// The first pass of type `T`, e.g. to reach the `GBufferRenderer`.
+    pub fn find_mut<T: DynPass>(&mut self) -> Option<&mut T> {
+        self.passes
+            .iter_mut()
+            .find_map(|pass| (pass.as_mut() as &mut dyn Any).downcast_mut::<T>())
+    }
+
+    pub fn process(
+        &mut self,
+        display: &dyn Display,
+        scene: (&World, &Camera),
+    ) -> anyhow::Result<&Texture2d> {
+        let dimensions = display.get_framebuffer_dimensions();
+        if self.dimensions != Some(dimensions) {
+            self.resources.allocate(display, &self.slots, dimensions)?;
+            self.dimensions = Some(dimensions);
+        }
+        for pass in &mut self.passes {
+            pass.process(display, &self.resources, scene)
+                .with_context(|| format!("pass `{}` failed", pass.output()))?;
+        }
+        self.resources.texture(&self.output)
+    }
+}
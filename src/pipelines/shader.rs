@@ -0,0 +1,127 @@
+use std::{
+    fs,
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::SystemTime,
+};
+
+use super::Display;
+
+static SHADER_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Makes programs created from now on load their stages from `dir` instead
+/// of the copies built into the binary, and rebuild when those files change.
+pub fn watch_directory(dir: PathBuf) {
+    let _ = SHADER_DIR.set(dir);
+}
+
+/// One stage of a program: its file name in `shaders/` and the source that
+/// was baked in at build time.
+#[derive(Debug, Clone, Copy)]
+pub struct ShaderSource {
+    pub file: &'static str,
+    pub embedded: &'static str,
+}
+
+/// A `glium::Program` that can be rebuilt from `shaders/` at runtime; see
+/// `watch_directory`. Without a watched directory it only ever holds the
+/// embedded sources.
+pub struct ShaderProgram {
+    vertex: ShaderSource,
+    fragment: ShaderSource,
+    geometry: Option<ShaderSource>,
+    program: glium::Program,
+    modified: Vec<Option<SystemTime>>,
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+impl ShaderProgram {
+    pub fn new(
+        display: &dyn Display,
+        vertex: ShaderSource,
+        fragment: ShaderSource,
+        geometry: Option<ShaderSource>,
+    ) -> Result<Self, glium::ProgramCreationError> {
+        let mut res = Self {
+            vertex,
+            fragment,
+            geometry,
+            program: glium::Program::from_source(
+                display,
+                vertex.embedded,
+                fragment.embedded,
+                geometry.map(|source| source.embedded),
+            )?,
+            modified: Vec::new(),
+        };
+        if let Some(dir) = SHADER_DIR.get() {
+            res.modified = res.stamps(dir);
+            res.rebuild(display, dir);
+        }
+        Ok(res)
+    }
+
+    fn stages(&self) -> impl Iterator<Item = ShaderSource> {
+        std::iter::once(self.vertex)
+            .chain(std::iter::once(self.fragment))
+            .chain(self.geometry)
+    }
+
+    fn stamps(&self, dir: &Path) -> Vec<Option<SystemTime>> {
+        self.stages()
+            .map(|source| modified(&dir.join(source.file)))
+            .collect()
+    }
+
+    fn compile(&self, display: &dyn Display, dir: &Path) -> anyhow::Result<glium::Program> {
+        let read = |source: &ShaderSource| {
+            let path = dir.join(source.file);
+            fs::read_to_string(&path)
+                .map_err(|err| anyhow::anyhow!("failed to read {}: {}", path.display(), err))
+        };
+        let geometry = self.geometry.as_ref().map(read).transpose()?;
+        Ok(glium::Program::from_source(
+            display,
+            &read(&self.vertex)?,
+            &read(&self.fragment)?,
+            geometry.as_deref(),
+        )?)
+    }
+
+    /// Swaps in a program built from the files in `dir`, keeping the current
+    /// one and printing the compiler log if that fails.
+    fn rebuild(&mut self, display: &dyn Display, dir: &Path) {
+        let names: Vec<_> = self.stages().map(|source| source.file).collect();
+        match self.compile(display, dir) {
+            Ok(program) => {
+                self.program = program;
+                eprintln!("loaded {}", names.join(", "));
+            }
+            Err(err) => eprintln!("keeping previous {}: {}", names.join(", "), err),
+        }
+    }
+
+    /// Rebuilds the program if any of its files changed since the last
+    /// check. Does nothing unless a directory is being watched.
+    pub fn reload(&mut self, display: &dyn Display) {
+        if let Some(dir) = SHADER_DIR.get() {
+            let stamps = self.stamps(dir);
+            if stamps != self.modified {
+                self.modified = stamps;
+                self.rebuild(display, dir);
+            }
+        }
+    }
+}
+
+impl Deref for ShaderProgram {
+    type Target = glium::Program;
+
+    fn deref(&self) -> &glium::Program {
+        &self.program
+    }
+}
@@ -0,0 +1,185 @@
+use glium::{implement_uniform_block, uniform, Surface};
+
+use crate::postprocess_shader_program;
+
+use super::{
+    gbuffer_pass::TextureGroup as GBufferTextureGroup, postprocess::*, shader::ShaderProgram,
+    Display, ProcessPass, SurfaceInstance, SurfaceProvider,
+};
+
+/// The most samples the kernel in `ssao.frag` holds.
+pub const MAX_KERNEL_SIZE: usize = 64;
+
+#[derive(Debug, Clone, Copy)]
+pub struct SsaoBlock {
+    kernel: [[f32; 4]; MAX_KERNEL_SIZE],
+    kernel_size: i32,
+    radius: f32,
+    bias: f32,
+}
+
+implement_uniform_block!(SsaoBlock, kernel, kernel_size, radius, bias);
+
+/// `size` samples in the unit hemisphere around +z, along a golden angle
+/// spiral and packed towards the centre, where occluders matter most.
+fn kernel(size: usize) -> [[f32; 4]; MAX_KERNEL_SIZE] {
+    let mut kernel = [[0.0; 4]; MAX_KERNEL_SIZE];
+    for (i, sample) in kernel.iter_mut().take(size).enumerate() {
+        let height = 1.0 - (i as f32 + 0.5) / size as f32;
+        let spread = (1.0 - height * height).sqrt();
+        let angle = i as f32 * 2.399_963;
+        let distance = (i as u32).reverse_bits() as f32 / u32::MAX as f32;
+        let scale = 0.1 + 0.9 * distance * distance;
+        *sample = [
+            angle.cos() * spread * scale,
+            angle.sin() * spread * scale,
+            height * scale,
+            0.0,
+        ];
+    }
+    kernel
+}
+
+/// Screen-space ambient occlusion from the G-buffer's eye-space position
+/// and normal. The occlusion is box blurred to hide the per-pixel kernel
+/// rotation, then multiplied into the G-buffer color in place, so passes
+/// after it see the shaded color.
+pub struct SsaoPass {
+    vertex: glium::VertexBuffer<PostProcessVertex>,
+    program: ShaderProgram,
+    blur_program: ShaderProgram,
+    occlusion: PostProcessProvider,
+    block: glium::uniforms::UniformBuffer<SsaoBlock>,
+    kernel_size: usize,
+    radius: f32,
+    bias: f32,
+    blur: u32,
+    projection: glam::Mat4,
+    dirty: bool,
+}
+
+impl SsaoPass {
+    pub fn new(display: &dyn Display) -> anyhow::Result<Self> {
+        let mut res = Self {
+            vertex: PostProcessVertex::get_buffer(display)?,
+            program: postprocess_shader_program!(display, "ssao")?,
+            blur_program: postprocess_shader_program!(display, "ssao_blur")?,
+            occlusion: PostProcessProvider::with_format(
+                display,
+                glium::texture::UncompressedFloatFormat::U8,
+            )?,
+            block: glium::uniforms::UniformBuffer::empty(display)?,
+            kernel_size: 32,
+            radius: 1.0,
+            bias: 0.1,
+            blur: 2,
+            projection: glam::Mat4::IDENTITY,
+            dirty: true,
+        };
+        res.upload();
+        Ok(res)
+    }
+
+    fn upload(&mut self) {
+        self.block.write(&SsaoBlock {
+            kernel: kernel(self.kernel_size),
+            kernel_size: self.kernel_size as i32,
+            radius: self.radius,
+            bias: self.bias,
+        });
+        self.dirty = false;
+    }
+
+    /// Number of samples taken per pixel, up to `MAX_KERNEL_SIZE`.
+    pub fn set_kernel_size(&mut self, size: usize) {
+        self.kernel_size = size.clamp(1, MAX_KERNEL_SIZE);
+        self.dirty = true;
+    }
+
+    /// How far around a point to look for occluders, in voxels.
+    pub fn set_radius(&mut self, radius: f32) {
+        self.radius = radius;
+        self.dirty = true;
+    }
+
+    /// Depth difference below which a sample doesn't count as occluded,
+    /// which keeps flat faces from shading themselves.
+    pub fn set_bias(&mut self, bias: f32) {
+        self.bias = bias;
+        self.dirty = true;
+    }
+
+    /// Half the width of the blur box; 2 covers the 4x4 rotation pattern
+    /// and 0 turns the blur off.
+    pub fn set_blur(&mut self, blur: u32) {
+        self.blur = blur;
+    }
+
+    /// The projection the G-buffer was drawn with, to find the samples on
+    /// screen.
+    pub fn set_projection(&mut self, projection: glam::Mat4) {
+        self.projection = projection;
+    }
+}
+
+impl<'pass> ProcessPass<'pass, &'pass GBufferTextureGroup> for SsaoPass {
+    type Output = &'pass GBufferTextureGroup;
+
+    fn process(
+        &'pass mut self,
+        display: &'pass dyn Display,
+        input: &'pass GBufferTextureGroup,
+    ) -> anyhow::Result<Self::Output> {
+        self.program.reload(display);
+        self.blur_program.reload(display);
+        if self.dirty {
+            self.upload();
+        }
+        let mut target = self.occlusion.get(display)?;
+        let uniforms = uniform! {
+            position_sample: input.position.sampled()
+                .magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest)
+                .minify_filter(glium::uniforms::MinifySamplerFilter::Nearest)
+                .wrap_function(glium::uniforms::SamplerWrapFunction::Clamp),
+            normal_sample: input.normal.sampled()
+                .magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest)
+                .minify_filter(glium::uniforms::MinifySamplerFilter::Nearest),
+            projection: self.projection.to_cols_array_2d(),
+            block: &self.block,
+        };
+        target.surface().draw(
+            self.vertex.slice(..).unwrap(),
+            glium::index::NoIndices(glium::index::PrimitiveType::TriangleStrip),
+            &self.program,
+            &uniforms,
+            &Default::default(),
+        )?;
+
+        let uniforms = uniform! {
+            occlusion_sample: target.output(),
+            blur: self.blur as i32,
+        };
+        let multiply = glium::Blend {
+            color: glium::BlendingFunction::Addition {
+                source: glium::LinearBlendingFactor::DestinationColor,
+                destination: glium::LinearBlendingFactor::Zero,
+            },
+            alpha: glium::BlendingFunction::Addition {
+                source: glium::LinearBlendingFactor::Zero,
+                destination: glium::LinearBlendingFactor::One,
+            },
+            constant_value: (0.0, 0.0, 0.0, 0.0),
+        };
+        glium::framebuffer::SimpleFrameBuffer::new(display, &input.color)?.draw(
+            self.vertex.slice(..).unwrap(),
+            glium::index::NoIndices(glium::index::PrimitiveType::TriangleStrip),
+            &self.blur_program,
+            &uniforms,
+            &glium::DrawParameters {
+                blend: multiply,
+                ..Default::default()
+            },
+        )?;
+        Ok(input)
+    }
+}
@@ -2,31 +2,63 @@ use glium::implement_uniform_block;
 
 use crate::postprocess_shader_program;
 
-use super::postprocess::SimplePostProcessPipeline;
+use super::{postprocess::SimplePostProcessPipeline, shader::ShaderProgram, Display};
 
-pub struct StrengthenPass;
+/// Glow around the outlines, fading out between `near` and `far` in view
+/// depth and scaled by `strength`.
+#[derive(Debug, Clone, Copy)]
+pub struct StrengthenPass {
+    near: f32,
+    far: f32,
+    strength: f32,
+}
 
 #[derive(Debug, Clone, Copy)]
 pub struct StrengthenBlock {
     near: f32,
     far: f32,
+    strength: f32,
 }
 
-implement_uniform_block!(StrengthenBlock, near, far);
+implement_uniform_block!(StrengthenBlock, near, far, strength);
+
+impl StrengthenPass {
+    pub fn set_depth_range(&mut self, (near, far): (f32, f32)) {
+        self.near = near;
+        self.far = far;
+    }
+
+    pub fn strength(&self) -> f32 {
+        self.strength
+    }
+
+    pub fn set_strength(&mut self, strength: f32) {
+        self.strength = strength.max(0.0);
+    }
+}
+
+impl Default for StrengthenPass {
+    fn default() -> Self {
+        Self {
+            near: -10.0,
+            far: 38.0,
+            strength: 1.0,
+        }
+    }
+}
 
 impl SimplePostProcessPipeline for StrengthenPass {
     type Block = StrengthenBlock;
 
-    fn load_shader(
-        display: &glium::Display,
-    ) -> Result<glium::Program, glium::ProgramCreationError> {
+    fn load_shader(display: &dyn Display) -> Result<ShaderProgram, glium::ProgramCreationError> {
         postprocess_shader_program!(display, "strengthen")
     }
 
-    fn get_block() -> Self::Block {
+    fn get_block(&self) -> Self::Block {
         StrengthenBlock {
-            near: -10.0,
-            far: 38.0,
+            near: self.near,
+            far: self.far,
+            strength: self.strength,
         }
     }
 }
@@ -0,0 +1,85 @@
+use std::{fs, path::Path};
+
+use serde::Serialize;
+
+/// One rendered view in the sheet; `yaw` is the turntable angle from the
+/// starting view and `pitch` the camera pitch, both in degrees.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Frame {
+    pub x: u32,
+    pub y: u32,
+    #[serde(rename = "w")]
+    pub width: u32,
+    #[serde(rename = "h")]
+    pub height: u32,
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+/// Frames laid out one row per pitch, one column per yaw.
+#[derive(Debug, Clone)]
+pub struct SpriteSheet {
+    pub columns: u32,
+    pub rows: u32,
+    pub frame_size: (u32, u32),
+    pub frames: Vec<Frame>,
+}
+
+/// What the atlas file holds: the sheet PNG it describes and every frame.
+#[derive(Serialize)]
+struct Atlas<'a> {
+    image: &'a str,
+    frame_width: u32,
+    frame_height: u32,
+    frames: &'a [Frame],
+}
+
+impl SpriteSheet {
+    pub fn new((width, height): (u32, u32), yaws: u32, pitches: &[f32]) -> Self {
+        let yaws = yaws.max(1);
+        let frames = pitches
+            .iter()
+            .enumerate()
+            .flat_map(|(row, &pitch)| {
+                (0..yaws).map(move |column| Frame {
+                    x: column * width,
+                    y: row as u32 * height,
+                    width,
+                    height,
+                    yaw: column as f32 * 360.0 / yaws as f32,
+                    pitch,
+                })
+            })
+            .collect();
+        Self {
+            columns: yaws,
+            rows: pitches.len() as u32,
+            frame_size: (width, height),
+            frames,
+        }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        let (width, height) = self.frame_size;
+        (width * self.columns, height * self.rows)
+    }
+
+    /// Writes the atlas as RON if `path` ends in `.ron`, JSON otherwise.
+    /// `image` is how the atlas refers to the sheet PNG.
+    pub fn write_atlas<P: AsRef<Path>>(&self, path: P, image: &str) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let (frame_width, frame_height) = self.frame_size;
+        let atlas = Atlas {
+            image,
+            frame_width,
+            frame_height,
+            frames: &self.frames,
+        };
+        let atlas = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("ron") => ron::ser::to_string_pretty(&atlas, Default::default())?,
+            _ => serde_json::to_string_pretty(&atlas)?,
+        };
+        fs::write(path, atlas)?;
+        Ok(())
+    }
+}
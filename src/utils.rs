@@ -1,29 +1,46 @@
 #[macro_export]
+macro_rules! shader_source {
+    ($file:expr) => {
+        $crate::pipelines::shader::ShaderSource {
+            file: $file,
+            embedded: include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/shaders/", $file)),
+        }
+    };
+}
+#[macro_export]
 macro_rules! shader_program {
     ($display:expr, $shader:literal) => {
-        glium::Program::from_source(
+        $crate::pipelines::shader::ShaderProgram::new(
+            $display,
+            $crate::shader_source!(concat!($shader, ".vert")),
+            $crate::shader_source!(concat!($shader, ".frag")),
+            None,
+        )
+    };
+    ($display:expr, $vertex:literal, $fragment:literal) => {
+        $crate::pipelines::shader::ShaderProgram::new(
             $display,
-            include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/shaders/", $shader, ".vert")),
-            include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/shaders/", $shader, ".frag")),
+            $crate::shader_source!(concat!($vertex, ".vert")),
+            $crate::shader_source!(concat!($fragment, ".frag")),
             None,
         )
     };
     ($display:expr, $shader:literal with geometry) => {
-        glium::Program::from_source(
+        $crate::pipelines::shader::ShaderProgram::new(
             $display,
-            include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/shaders/", $shader, ".vert")),
-            include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/shaders/", $shader, ".frag")),
-            Some(include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/shaders/", $shader, ".geom"))),
+            $crate::shader_source!(concat!($shader, ".vert")),
+            $crate::shader_source!(concat!($shader, ".frag")),
+            Some($crate::shader_source!(concat!($shader, ".geom"))),
         )
     };
 }
 #[macro_export]
 macro_rules! postprocess_shader_program {
     ($display:expr, $shader:literal) => {
-        glium::Program::from_source(
+        $crate::pipelines::shader::ShaderProgram::new(
             $display,
-            include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/shaders/postprocess.vert")),
-            include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/shaders/", $shader, ".frag")),
+            $crate::shader_source!("postprocess.vert"),
+            $crate::shader_source!(concat!($shader, ".frag")),
             None,
         )
     };
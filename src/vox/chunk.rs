@@ -0,0 +1,191 @@
+use super::VoxError;
+
+pub struct Chunk<'a> {
+    pub id: &'a [u8],
+    pub content: &'a [u8],
+    pub children: &'a [u8],
+}
+
+impl<'a> Chunk<'a> {
+    pub fn name(&self) -> String {
+        String::from_utf8_lossy(self.id).into_owned()
+    }
+
+    pub fn children(&self) -> ChunkIter<'a> {
+        ChunkIter(Reader::new(self.children, "child chunk"))
+    }
+}
+
+pub struct ChunkIter<'a>(Reader<'a>);
+
+impl<'a> Iterator for ChunkIter<'a> {
+    type Item = Result<Chunk<'a>, VoxError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.0.is_empty() {
+            None
+        } else {
+            Some(self.0.chunk())
+        }
+    }
+}
+
+pub struct Reader<'a> {
+    data: &'a [u8],
+    context: &'static str,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8], context: &'static str) -> Self {
+        Self { data, context }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], VoxError> {
+        if self.data.len() < len {
+            return Err(VoxError::Parse(format!("truncated {}", self.context)));
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Ok(head)
+    }
+
+    pub fn u32(&mut self) -> Result<u32, VoxError> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub fn i32(&mut self) -> Result<i32, VoxError> {
+        Ok(self.u32()? as i32)
+    }
+
+    pub fn string(&mut self) -> Result<String, VoxError> {
+        let len = self.u32()? as usize;
+        Ok(String::from_utf8_lossy(self.bytes(len)?).into_owned())
+    }
+
+    pub fn dict(&mut self) -> Result<dot_vox::Dict, VoxError> {
+        let count = self.u32()?;
+        let mut dict = dot_vox::Dict::new();
+        for _ in 0..count {
+            let key = self.string()?;
+            dict.insert(key, self.string()?);
+        }
+        Ok(dict)
+    }
+
+    pub fn chunk(&mut self) -> Result<Chunk<'a>, VoxError> {
+        let id = self.bytes(4)?;
+        let truncated =
+            || VoxError::Parse(format!("truncated {} chunk", String::from_utf8_lossy(id)));
+        let content_len = self.u32().map_err(|_| truncated())? as usize;
+        let children_len = self.u32().map_err(|_| truncated())? as usize;
+        Ok(Chunk {
+            id,
+            content: self.bytes(content_len).map_err(|_| truncated())?,
+            children: self.bytes(children_len).map_err(|_| truncated())?,
+        })
+    }
+}
+
+fn check_chunk(chunk: &Chunk) -> Result<(), VoxError> {
+    let mut content = Reader::new(chunk.content, "chunk content");
+    match chunk.id {
+        b"SIZE" => {
+            content.bytes(12)?;
+        }
+        b"XYZI" => {
+            let count = content.u32()? as usize;
+            content.bytes(count.saturating_mul(4))?;
+        }
+        b"RGBA" if !chunk.content.len().is_multiple_of(4) => {
+            return Err(VoxError::Parse("RGBA chunk is not a whole palette".into()));
+        }
+        b"MATL" => {
+            content.u32()?;
+            content.dict()?;
+        }
+        b"PACK" => {
+            content.u32()?;
+        }
+        _ => (),
+    }
+
+    for child in chunk.children() {
+        let child = child?;
+        check_chunk(&child).map_err(|err| match err {
+            VoxError::Parse(reason) => {
+                VoxError::Parse(format!("{} in {} chunk", reason, child.name()))
+            }
+            err => err,
+        })?;
+    }
+    Ok(())
+}
+
+pub fn main_chunk(data: &[u8]) -> Result<(u32, Chunk<'_>), VoxError> {
+    let mut reader = Reader::new(data, "file header");
+    if reader.bytes(4)? != b"VOX " {
+        return Err(VoxError::Parse("missing VOX magic number".into()));
+    }
+    let version = reader.u32()?;
+    let main = reader.chunk()?;
+    if main.id != b"MAIN" {
+        return Err(VoxError::Parse(format!(
+            "expected MAIN chunk, found {}",
+            main.name()
+        )));
+    }
+    Ok((version, main))
+}
+
+pub fn check_structure(data: &[u8]) -> Result<u32, VoxError> {
+    let (version, main) = main_chunk(data)?;
+    check_chunk(&main)?;
+    Ok(version)
+}
+
+#[derive(Default)]
+pub struct Writer(Vec<u8>);
+
+impl Writer {
+    pub fn into_inner(self) -> Vec<u8> {
+        self.0
+    }
+
+    pub fn bytes(&mut self, data: &[u8]) -> &mut Self {
+        self.0.extend_from_slice(data);
+        self
+    }
+
+    pub fn u32(&mut self, value: u32) -> &mut Self {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    pub fn i32(&mut self, value: i32) -> &mut Self {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    pub fn string(&mut self, value: &str) -> &mut Self {
+        self.u32(value.len() as u32).bytes(value.as_bytes())
+    }
+
+    pub fn dict(&mut self, entries: &[(&str, &str)]) -> &mut Self {
+        self.u32(entries.len() as u32);
+        for (key, value) in entries {
+            self.string(key).string(value);
+        }
+        self
+    }
+
+    pub fn chunk(&mut self, id: &[u8; 4], content: &[u8], children: &[u8]) -> &mut Self {
+        self.bytes(id)
+            .u32(content.len() as u32)
+            .u32(children.len() as u32)
+            .bytes(content)
+            .bytes(children)
+    }
+}
@@ -0,0 +1,346 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+};
+
+use crate::world::{Material, SolidBlock, World};
+
+use super::{chunk::Writer, material};
+
+const MODEL_SIZE: u32 = 256;
+const PALETTE_SIZE: usize = 255;
+
+type Color = [u8; 3];
+
+type Entry = (SolidBlock, usize);
+
+fn color(blk: &SolidBlock) -> Color {
+    let (r, g, b) = blk.rgb();
+    [r, g, b]
+}
+
+fn channel_range(entries: &[Entry], channel: usize) -> u8 {
+    let (min, max) = entries
+        .iter()
+        .fold((u8::MAX, u8::MIN), |(min, max), (blk, _)| {
+            (min.min(color(blk)[channel]), max.max(color(blk)[channel]))
+        });
+    max.saturating_sub(min)
+}
+
+fn average(entries: &[Entry]) -> Color {
+    let total: usize = entries.iter().map(|(_, count)| count).sum();
+    let mut sum = [0usize; 3];
+    for (blk, count) in entries {
+        for (channel, value) in color(blk).iter().enumerate() {
+            sum[channel] += *value as usize * count;
+        }
+    }
+    let total = total.max(1);
+    [
+        ((sum[0] + total / 2) / total) as u8,
+        ((sum[1] + total / 2) / total) as u8,
+        ((sum[2] + total / 2) / total) as u8,
+    ]
+}
+
+/// Splits the blocks into at most `target` boxes, halving the box with the
+/// widest colour channel each time. Boxes of a single colour still split
+/// once nothing wider is left, so same-coloured blocks with different
+/// materials keep separate entries while there's room.
+fn median_cut(entries: Vec<Entry>, target: usize) -> Vec<Vec<Entry>> {
+    let mut boxes = vec![entries];
+    while boxes.len() < target {
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, entries)| entries.len() > 1)
+            .flat_map(|(index, entries)| {
+                (0..3).map(move |channel| {
+                    let key = (channel_range(entries, channel), entries.len());
+                    (index, channel, key)
+                })
+            })
+            .max_by_key(|(_, _, key)| *key);
+        let (index, channel) = match widest {
+            Some((index, channel, _)) => (index, channel),
+            None => break,
+        };
+
+        let mut entries = boxes.swap_remove(index);
+        // Stable, so a single-coloured box stays sorted by material.
+        entries.sort_by_key(|(blk, _)| color(blk)[channel]);
+        let half = entries.iter().map(|(_, count)| count).sum::<usize>() / 2;
+        let mut seen = 0;
+        let split = entries
+            .iter()
+            .position(|(_, count)| {
+                seen += count;
+                seen > half
+            })
+            .unwrap_or(0)
+            .clamp(1, entries.len() - 1);
+        let upper = entries.split_off(split);
+        boxes.push(entries);
+        boxes.push(upper);
+    }
+    boxes
+}
+
+struct Palette {
+    colors: Vec<Color>,
+    materials: Vec<Material>,
+    lookup: HashMap<SolidBlock, u8>,
+}
+
+impl Palette {
+    fn new(world: &World) -> Self {
+        let mut counts = HashMap::<SolidBlock, usize>::new();
+        for (_, blk) in world.iter() {
+            *counts.entry(*blk).or_default() += 1;
+        }
+
+        let mut distinct: Vec<_> = counts.into_iter().collect();
+        distinct.sort();
+        if distinct.len() <= PALETTE_SIZE {
+            return Self {
+                colors: distinct.iter().map(|(blk, _)| color(blk)).collect(),
+                materials: distinct.iter().map(|(blk, _)| *blk.material()).collect(),
+                lookup: distinct
+                    .iter()
+                    .enumerate()
+                    .map(|(index, (blk, _))| (*blk, index as u8))
+                    .collect(),
+            };
+        }
+
+        let boxes = median_cut(distinct, PALETTE_SIZE);
+        let mut res = Self {
+            colors: Vec::with_capacity(boxes.len()),
+            materials: Vec::with_capacity(boxes.len()),
+            lookup: HashMap::new(),
+        };
+        for (index, entries) in boxes.iter().enumerate() {
+            res.colors.push(average(entries));
+            // Each merged entry keeps the material of its most common block.
+            let (common, _) = entries
+                .iter()
+                .max_by_key(|(_, count)| *count)
+                .expect("median cut boxes are never empty");
+            res.materials.push(*common.material());
+            res.lookup
+                .extend(entries.iter().map(|(blk, _)| (*blk, index as u8)));
+        }
+        res
+    }
+
+    fn write(&self, writer: &mut Writer) {
+        let mut content = Writer::default();
+        for index in 0..=PALETTE_SIZE {
+            let [r, g, b] = self.colors.get(index).copied().unwrap_or_default();
+            content.bytes(&[r, g, b, 0xFF]);
+        }
+        writer.chunk(b"RGBA", &content.into_inner(), &[]);
+
+        for (index, material) in self.materials.iter().enumerate() {
+            let properties = material::to_properties(material);
+            let properties: Vec<_> = properties
+                .iter()
+                .map(|(key, value)| (*key, value.as_str()))
+                .collect();
+            let mut content = Writer::default();
+            content.u32(index as u32 + 1).dict(&properties);
+            writer.chunk(b"MATL", &content.into_inner(), &[]);
+        }
+    }
+}
+
+struct Model {
+    origin: [u32; 3],
+    size: [u32; 3],
+    voxels: Vec<[u8; 4]>,
+}
+
+impl Model {
+    fn new(key: [u32; 3], dims: [u32; 3]) -> Self {
+        let origin = [
+            key[0] * MODEL_SIZE,
+            key[1] * MODEL_SIZE,
+            key[2] * MODEL_SIZE,
+        ];
+        let size = [
+            (dims[0] - origin[0]).clamp(1, MODEL_SIZE),
+            (dims[1] - origin[1]).clamp(1, MODEL_SIZE),
+            (dims[2] - origin[2]).clamp(1, MODEL_SIZE),
+        ];
+        Self {
+            origin,
+            size,
+            voxels: Vec::new(),
+        }
+    }
+
+    fn write(&self, writer: &mut Writer) {
+        let mut size = Writer::default();
+        size.u32(self.size[0]).u32(self.size[1]).u32(self.size[2]);
+        writer.chunk(b"SIZE", &size.into_inner(), &[]);
+
+        let mut xyzi = Writer::default();
+        xyzi.u32(self.voxels.len() as u32);
+        for voxel in &self.voxels {
+            xyzi.bytes(voxel);
+        }
+        writer.chunk(b"XYZI", &xyzi.into_inner(), &[]);
+    }
+
+    // Inverse of `Transform::place`: the importer pivots around the centre
+    // of the model, so shift the origin by half the size.
+    fn translation(&self) -> String {
+        let axis = |i: usize| self.origin[i] + self.size[i] / 2;
+        format!("{} {} {}", axis(0), axis(1), axis(2))
+    }
+}
+
+fn write_transform(writer: &mut Writer, id: u32, child: u32, layer: i32, frame: &[(&str, &str)]) {
+    let mut content = Writer::default();
+    content
+        .u32(id)
+        .dict(&[])
+        .u32(child)
+        .i32(-1)
+        .i32(layer)
+        .u32(1)
+        .dict(frame);
+    writer.chunk(b"nTRN", &content.into_inner(), &[]);
+}
+
+fn write_scene(writer: &mut Writer, models: &[Model]) {
+    write_transform(writer, 0, 1, -1, &[]);
+
+    let mut group = Writer::default();
+    group.u32(1).dict(&[]).u32(models.len() as u32);
+    for index in 0..models.len() as u32 {
+        group.u32(2 + index * 2);
+    }
+    writer.chunk(b"nGRP", &group.into_inner(), &[]);
+
+    for (index, model) in models.iter().enumerate() {
+        let id = 2 + index as u32 * 2;
+        write_transform(writer, id, id + 1, 0, &[("_t", &model.translation())]);
+
+        let mut shape = Writer::default();
+        shape
+            .u32(id + 1)
+            .dict(&[])
+            .u32(1)
+            .u32(index as u32)
+            .dict(&[]);
+        writer.chunk(b"nSHP", &shape.into_inner(), &[]);
+    }
+}
+
+pub fn save<W: io::Write>(world: &World, writer: &mut W) -> io::Result<()> {
+    let palette = Palette::new(world);
+    let dims = world.dims();
+    let dims = [dims.0, dims.2, dims.1];
+
+    let mut models = BTreeMap::new();
+    for (pos, blk) in world.iter() {
+        let pos = [pos.0, pos.2, pos.1];
+        let key = [
+            pos[0] / MODEL_SIZE,
+            pos[1] / MODEL_SIZE,
+            pos[2] / MODEL_SIZE,
+        ];
+        let local = |axis: usize| (pos[axis] % MODEL_SIZE) as u8;
+        models
+            .entry(key)
+            .or_insert_with(|| Model::new(key, dims))
+            .voxels
+            .push([local(0), local(1), local(2), palette.lookup[blk] + 1]);
+    }
+    let mut models: Vec<_> = models.into_values().collect();
+    if models.is_empty() {
+        models.push(Model::new([0, 0, 0], dims));
+    }
+
+    let mut children = Writer::default();
+    for model in &models {
+        model.write(&mut children);
+    }
+    write_scene(&mut children, &models);
+    palette.write(&mut children);
+
+    let mut file = Writer::default();
+    file.bytes(b"VOX ")
+        .u32(150)
+        .chunk(b"MAIN", &[], &children.into_inner());
+    writer.write_all(&file.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::world::{Block, MaterialKind, WorldPosition};
+
+    use super::*;
+
+    fn blocks(world: &World) -> Vec<((u32, u32, u32), SolidBlock)> {
+        let mut res: Vec<_> = world
+            .iter()
+            .map(|(WorldPosition(x, y, z), blk)| ((x, y, z), *blk))
+            .collect();
+        res.sort();
+        res
+    }
+
+    fn round_trip(world: &World) -> World {
+        let mut data = Vec::new();
+        save(world, &mut data).unwrap();
+        World::from_vox(&data).unwrap()
+    }
+
+    #[test]
+    fn bundled_assets_round_trip() {
+        let assets: [(&str, &[u8]); 4] = [
+            ("doom", include_bytes!("../../assets/doom.vox")),
+            ("room", include_bytes!("../../assets/room.vox")),
+            ("teapot", include_bytes!("../../assets/teapot.vox")),
+            ("test", include_bytes!("../../assets/test.vox")),
+        ];
+        for (name, data) in assets {
+            let world = World::from_vox(data).unwrap();
+            let saved = round_trip(&world);
+            assert_eq!(saved.dims(), world.dims(), "{}", name);
+            assert!(blocks(&saved) == blocks(&world), "{} changed", name);
+        }
+    }
+
+    #[test]
+    fn merged_palette_keeps_materials_apart() {
+        // 10 colours in 30 materials each: more blocks than palette entries,
+        // but few enough colours that every one survives exactly.
+        let mut world = World::new((30, 10, 1));
+        let colors: Vec<Color> = (0..10).map(|i| [i * 25, 255 - i * 25, 128]).collect();
+        for (y, [r, g, b]) in colors.iter().copied().enumerate() {
+            for x in 0..30 {
+                let material = Material::new(MaterialKind::Metal, x as f32 / 30.0, 0.0, 0.0, 1.3);
+                let pos = WorldPosition(x, y as u32, 0);
+                world.set(pos, Block::solid(r, g, b).with_material(material));
+            }
+        }
+
+        let palette = Palette::new(&world);
+        assert_eq!(palette.colors.len(), PALETTE_SIZE);
+        let entries: HashSet<_> = palette.colors.iter().zip(&palette.materials).collect();
+        assert_eq!(entries.len(), PALETTE_SIZE, "duplicate palette entries");
+
+        let saved = round_trip(&world);
+        let distinct: HashSet<_> = saved.iter().map(|(_, blk)| *blk).collect();
+        assert_eq!(distinct.len(), PALETTE_SIZE);
+        for ((pos, before), (_, after)) in blocks(&world).iter().zip(blocks(&saved).iter()) {
+            assert_eq!(before.rgb(), after.rgb(), "{:?}", pos);
+        }
+    }
+}
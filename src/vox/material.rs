@@ -0,0 +1,48 @@
+use crate::world::{Material, MaterialKind};
+
+fn property(properties: &dot_vox::Dict, key: &str) -> Option<f32> {
+    properties
+        .get(key)
+        .and_then(|value| value.trim().parse().ok())
+}
+
+pub fn from_properties(properties: &dot_vox::Dict) -> Material {
+    let default = Material::default();
+    let kind = properties
+        .get("_type")
+        .map_or(default.kind(), |kind| MaterialKind::from_vox(kind));
+    Material::new(
+        kind,
+        property(properties, "_rough").unwrap_or_else(|| default.roughness()),
+        property(properties, "_emit").unwrap_or_else(|| default.emission()),
+        property(properties, "_trans")
+            .or_else(|| property(properties, "_alpha"))
+            .unwrap_or_else(|| default.transparency()),
+        // MagicaVoxel stores the index of refraction minus one.
+        property(properties, "_ior").map_or(default.ior(), |ior| ior + 1.0),
+    )
+}
+
+pub fn to_properties(material: &Material) -> Vec<(&'static str, String)> {
+    vec![
+        ("_type", material.kind().to_vox().to_owned()),
+        ("_rough", material.roughness().to_string()),
+        ("_emit", material.emission().to_string()),
+        ("_trans", material.transparency().to_string()),
+        ("_ior", (material.ior() - 1.0).to_string()),
+    ]
+}
+
+pub fn resolve(palette_len: usize, materials: &[dot_vox::Material]) -> Vec<Material> {
+    let mut res = vec![Material::default(); palette_len];
+    for material in materials {
+        // Material ids follow the 1-based palette indices stored in the file.
+        if let Some(slot) = (material.id as usize)
+            .checked_sub(1)
+            .and_then(|index| res.get_mut(index))
+        {
+            *slot = from_properties(&material.properties);
+        }
+    }
+    res
+}
@@ -0,0 +1,160 @@
+use std::fmt;
+
+use crate::world::World;
+
+mod chunk;
+mod export;
+mod material;
+mod scene;
+
+pub use export::save;
+pub use scene::Scene;
+
+const MAX_REPORTED_ISSUES: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VoxError {
+    Parse(String),
+    NoModels,
+    PaletteIndexOutOfRange {
+        model: usize,
+        index: u8,
+        palette_len: usize,
+    },
+    VoxelOutOfBounds {
+        model: usize,
+        position: (u8, u8, u8),
+        size: (u32, u32, u32),
+    },
+}
+
+impl fmt::Display for VoxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VoxError::Parse(reason) => write!(f, "malformed .vox file: {}", reason),
+            VoxError::NoModels => write!(f, ".vox file contains no models"),
+            VoxError::PaletteIndexOutOfRange {
+                model,
+                index,
+                palette_len,
+            } => write!(
+                f,
+                "model {}: palette index {} out of range (palette has {} entries)",
+                model, index, palette_len
+            ),
+            VoxError::VoxelOutOfBounds {
+                model,
+                position: (x, y, z),
+                size: (sx, sy, sz),
+            } => write!(
+                f,
+                "model {}: voxel ({}, {}, {}) outside declared size {}x{}x{}",
+                model, x, y, z, sx, sy, sz
+            ),
+        }
+    }
+}
+
+impl std::error::Error for VoxError {}
+
+#[derive(Debug, Clone, Default)]
+pub struct VoxReport {
+    pub version: Option<u32>,
+    pub models: usize,
+    pub placements: usize,
+    pub voxels: usize,
+    pub issues: Vec<VoxError>,
+}
+
+impl VoxReport {
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl fmt::Display for VoxReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.version {
+            Some(version) => writeln!(
+                f,
+                ".vox version {}: {} model(s) placed {} time(s), {} voxel(s)",
+                version, self.models, self.placements, self.voxels
+            )?,
+            None => writeln!(f, "unreadable .vox file")?,
+        }
+        if self.is_valid() {
+            return writeln!(f, "no issues found");
+        }
+        writeln!(f, "{} issue(s):", self.issues.len())?;
+        for issue in self.issues.iter().take(MAX_REPORTED_ISSUES) {
+            writeln!(f, "  - {}", issue)?;
+        }
+        if self.issues.len() > MAX_REPORTED_ISSUES {
+            writeln!(
+                f,
+                "  ... and {} more",
+                self.issues.len() - MAX_REPORTED_ISSUES
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn parse(data: &[u8]) -> Result<dot_vox::DotVoxData, VoxError> {
+    chunk::check_structure(data)?;
+    dot_vox::load_bytes(data).map_err(|reason| VoxError::Parse(reason.into()))
+}
+
+fn check_model(
+    index: usize,
+    model: &dot_vox::Model,
+    palette_len: usize,
+) -> impl '_ + Iterator<Item = VoxError> {
+    let dot_vox::Size { x, y, z } = model.size;
+    model.voxels.iter().filter_map(move |voxel| {
+        let dot_vox::Voxel {
+            x: vx,
+            y: vy,
+            z: vz,
+            i,
+        } = *voxel;
+        if vx as u32 >= x || vy as u32 >= y || vz as u32 >= z {
+            Some(VoxError::VoxelOutOfBounds {
+                model: index,
+                position: (vx, vy, vz),
+                size: (x, y, z),
+            })
+        } else if i as usize >= palette_len {
+            Some(VoxError::PaletteIndexOutOfRange {
+                model: index,
+                index: i,
+                palette_len,
+            })
+        } else {
+            None
+        }
+    })
+}
+
+pub fn validate(data: &[u8]) -> VoxReport {
+    let mut report = VoxReport::default();
+    if let Ok(version) = chunk::check_structure(data) {
+        report.version = Some(version);
+    }
+    let scene = match Scene::parse(data) {
+        Ok(scene) => scene,
+        Err(err) => {
+            report.issues.push(err);
+            return report;
+        }
+    };
+    report.models = scene.models.len();
+    report.placements = scene.placements.len();
+    report.voxels = scene.models.iter().map(|model| model.voxels.len()).sum();
+    report.issues.extend(scene.check());
+    report
+}
+
+pub fn load(data: &[u8]) -> Result<World, VoxError> {
+    Scene::parse(data)?.to_world()
+}
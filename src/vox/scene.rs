@@ -0,0 +1,377 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::world::{Block, Material, World, WorldPosition};
+
+use super::{
+    check_model,
+    chunk::{main_chunk, Reader},
+    material, parse, VoxError,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rotation([[i32; 3]; 3]);
+
+impl Rotation {
+    pub const IDENTITY: Rotation = Rotation([[1, 0, 0], [0, 1, 0], [0, 0, 1]]);
+
+    fn from_byte(byte: u8) -> Result<Self, VoxError> {
+        let first = (byte & 0b11) as usize;
+        let second = (byte >> 2 & 0b11) as usize;
+        if first > 2 || second > 2 || first == second {
+            return Err(VoxError::Parse(format!("invalid rotation {:#010b}", byte)));
+        }
+        let third = 3 - first - second;
+        let sign = |bit: u8| if byte >> bit & 1 == 1 { -1 } else { 1 };
+        let mut rows = [[0; 3]; 3];
+        rows[0][first] = sign(4);
+        rows[1][second] = sign(5);
+        rows[2][third] = sign(6);
+        Ok(Self(rows))
+    }
+
+    pub fn apply(&self, [x, y, z]: [i32; 3]) -> [i32; 3] {
+        let Rotation(m) = self;
+        [
+            m[0][0] * x + m[0][1] * y + m[0][2] * z,
+            m[1][0] * x + m[1][1] * y + m[1][2] * z,
+            m[2][0] * x + m[2][1] * y + m[2][2] * z,
+        ]
+    }
+
+    fn compose(&self, rhs: &Rotation) -> Rotation {
+        let mut res = [[0; 3]; 3];
+        for (i, row) in res.iter_mut().enumerate() {
+            for (j, cell) in row.iter_mut().enumerate() {
+                *cell = (0..3).map(|k| self.0[i][k] * rhs.0[k][j]).sum();
+            }
+        }
+        Rotation(res)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transform {
+    pub rotation: Rotation,
+    pub translation: [i32; 3],
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform {
+        rotation: Rotation::IDENTITY,
+        translation: [0, 0, 0],
+    };
+
+    fn from_frame(frame: &dot_vox::Dict) -> Result<Self, VoxError> {
+        let rotation = match frame.get("_r") {
+            Some(value) => Rotation::from_byte(value.trim().parse().map_err(|_| {
+                VoxError::Parse(format!("invalid rotation attribute {:?}", value))
+            })?)?,
+            None => Rotation::IDENTITY,
+        };
+        let mut translation = [0; 3];
+        if let Some(value) = frame.get("_t") {
+            let mut parts = value.split_whitespace().map(str::parse::<i32>);
+            for axis in translation.iter_mut() {
+                *axis = match parts.next() {
+                    Some(Ok(v)) => v,
+                    _ => {
+                        return Err(VoxError::Parse(format!(
+                            "invalid translation attribute {:?}",
+                            value
+                        )))
+                    }
+                };
+            }
+        }
+        Ok(Self {
+            rotation,
+            translation,
+        })
+    }
+
+    fn compose(&self, child: &Transform) -> Transform {
+        let [x, y, z] = self.rotation.apply(child.translation);
+        let [tx, ty, tz] = self.translation;
+        Transform {
+            rotation: self.rotation.compose(&child.rotation),
+            translation: [x + tx, y + ty, z + tz],
+        }
+    }
+
+    // MagicaVoxel pivots a model around its centre, so work in doubled
+    // coordinates to keep odd sizes and mirrored axes exact.
+    pub fn place(&self, size: dot_vox::Size, (x, y, z): (u8, u8, u8)) -> [i32; 3] {
+        let centered = [
+            2 * x as i32 + 1 - size.x as i32,
+            2 * y as i32 + 1 - size.y as i32,
+            2 * z as i32 + 1 - size.z as i32,
+        ];
+        let rotated = self.rotation.apply(centered);
+        let mut res = [0; 3];
+        for axis in 0..3 {
+            res[axis] = (rotated[axis] + 2 * self.translation[axis]).div_euclid(2);
+        }
+        res
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PlacedModel {
+    pub model: usize,
+    pub transform: Transform,
+}
+
+enum Node {
+    Transform {
+        hidden: bool,
+        layer: i32,
+        child: u32,
+        transform: Transform,
+    },
+    Group(Vec<u32>),
+    Shape(Vec<u32>),
+}
+
+fn is_hidden(attributes: &dot_vox::Dict) -> bool {
+    attributes.get("_hidden").map(String::as_str) == Some("1")
+}
+
+fn parse_node(id: &[u8], content: &[u8]) -> Result<Option<(u32, Node)>, VoxError> {
+    let mut reader = Reader::new(content, "scene node");
+    let node = match id {
+        b"nTRN" => {
+            let node_id = reader.u32()?;
+            let attributes = reader.dict()?;
+            let child = reader.u32()?;
+            let _reserved = reader.i32()?;
+            let layer = reader.i32()?;
+            let frames = reader.u32()?;
+            let transform = if frames > 0 {
+                Transform::from_frame(&reader.dict()?)?
+            } else {
+                Transform::IDENTITY
+            };
+            (
+                node_id,
+                Node::Transform {
+                    hidden: is_hidden(&attributes),
+                    layer,
+                    child,
+                    transform,
+                },
+            )
+        }
+        b"nGRP" => {
+            let node_id = reader.u32()?;
+            reader.dict()?;
+            let count = reader.u32()?;
+            let children = (0..count).map(|_| reader.u32()).collect::<Result<_, _>>()?;
+            (node_id, Node::Group(children))
+        }
+        b"nSHP" => {
+            let node_id = reader.u32()?;
+            reader.dict()?;
+            let count = reader.u32()?;
+            let mut models = Vec::new();
+            for _ in 0..count {
+                models.push(reader.u32()?);
+                reader.dict()?;
+            }
+            (node_id, Node::Shape(models))
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(node))
+}
+
+pub struct Scene {
+    pub models: Vec<dot_vox::Model>,
+    pub palette: Vec<u32>,
+    pub materials: Vec<Material>,
+    pub placements: Vec<PlacedModel>,
+}
+
+struct SceneWalker<'a> {
+    nodes: &'a HashMap<u32, Node>,
+    hidden_layers: &'a HashSet<i32>,
+    placements: Vec<PlacedModel>,
+    depth: usize,
+}
+
+impl<'a> SceneWalker<'a> {
+    fn walk(&mut self, id: u32, parent: &Transform) -> Result<(), VoxError> {
+        self.depth += 1;
+        if self.depth > self.nodes.len() {
+            return Err(VoxError::Parse("cycle in scene graph".into()));
+        }
+        let node = self
+            .nodes
+            .get(&id)
+            .ok_or_else(|| VoxError::Parse(format!("missing scene node {}", id)))?;
+        match node {
+            Node::Transform {
+                hidden,
+                layer,
+                child,
+                transform,
+            } => {
+                if !*hidden && !self.hidden_layers.contains(layer) {
+                    self.walk(*child, &parent.compose(transform))?;
+                }
+            }
+            Node::Group(children) => {
+                for child in children {
+                    self.walk(*child, parent)?;
+                }
+            }
+            Node::Shape(models) => {
+                self.placements
+                    .extend(models.iter().map(|model| PlacedModel {
+                        model: *model as usize,
+                        transform: *parent,
+                    }));
+            }
+        }
+        self.depth -= 1;
+        Ok(())
+    }
+}
+
+impl Scene {
+    pub fn parse(data: &[u8]) -> Result<Self, VoxError> {
+        let dot_vox::DotVoxData {
+            models,
+            palette,
+            materials,
+            ..
+        } = parse(data)?;
+        if models.is_empty() {
+            return Err(VoxError::NoModels);
+        }
+
+        let (_, main) = main_chunk(data)?;
+        let mut nodes = HashMap::new();
+        let mut hidden_layers = HashSet::new();
+        for chunk in main.children() {
+            let chunk = chunk?;
+            if chunk.id == b"LAYR" {
+                let mut reader = Reader::new(chunk.content, "LAYR chunk");
+                let layer = reader.i32()?;
+                if is_hidden(&reader.dict()?) {
+                    hidden_layers.insert(layer);
+                }
+            } else if let Some((id, node)) = parse_node(chunk.id, chunk.content)? {
+                nodes.insert(id, node);
+            }
+        }
+
+        let placements = if nodes.is_empty() {
+            models
+                .iter()
+                .enumerate()
+                .map(|(model, dot_vox::Model { size, .. })| PlacedModel {
+                    model,
+                    transform: Transform {
+                        rotation: Rotation::IDENTITY,
+                        translation: [size.x as i32 / 2, size.y as i32 / 2, size.z as i32 / 2],
+                    },
+                })
+                .collect()
+        } else {
+            let mut walker = SceneWalker {
+                nodes: &nodes,
+                hidden_layers: &hidden_layers,
+                placements: Vec::new(),
+                depth: 0,
+            };
+            walker.walk(0, &Transform::IDENTITY)?;
+            walker.placements
+        };
+
+        if let Some(placed) = placements.iter().find(|p| p.model >= models.len()) {
+            return Err(VoxError::Parse(format!(
+                "shape node references missing model {}",
+                placed.model
+            )));
+        }
+
+        Ok(Self {
+            models,
+            materials: material::resolve(palette.len(), &materials),
+            palette,
+            placements,
+        })
+    }
+
+    pub fn check(&self) -> impl '_ + Iterator<Item = VoxError> {
+        self.models
+            .iter()
+            .enumerate()
+            .flat_map(move |(index, model)| check_model(index, model, self.palette.len()))
+    }
+
+    pub fn bounds(&self) -> Option<([i32; 3], [i32; 3])> {
+        self.placements
+            .iter()
+            .filter_map(|placed| {
+                let size = self.models[placed.model].size;
+                if size.x == 0 || size.y == 0 || size.z == 0 {
+                    return None;
+                }
+                let last = |v: u32| (v - 1).min(u8::MAX as u32) as u8;
+                let a = placed.transform.place(size, (0, 0, 0));
+                let b = placed
+                    .transform
+                    .place(size, (last(size.x), last(size.y), last(size.z)));
+                Some((
+                    [a[0].min(b[0]), a[1].min(b[1]), a[2].min(b[2])],
+                    [a[0].max(b[0]), a[1].max(b[1]), a[2].max(b[2])],
+                ))
+            })
+            .reduce(|(amin, amax), (bmin, bmax)| {
+                (
+                    [
+                        amin[0].min(bmin[0]),
+                        amin[1].min(bmin[1]),
+                        amin[2].min(bmin[2]),
+                    ],
+                    [
+                        amax[0].max(bmax[0]),
+                        amax[1].max(bmax[1]),
+                        amax[2].max(bmax[2]),
+                    ],
+                )
+            })
+    }
+
+    pub fn to_world(&self) -> Result<World, VoxError> {
+        if let Some(err) = self.check().next() {
+            return Err(err);
+        }
+        let (min, max) = match self.bounds() {
+            Some(bounds) => bounds,
+            None => return Ok(World::new((0, 0, 0))),
+        };
+        let extent = |axis: usize| (max[axis] - min[axis] + 1) as u32;
+        let mut res = World::new((extent(0), extent(2), extent(1)));
+
+        for placed in &self.placements {
+            let model = &self.models[placed.model];
+            for voxel in &model.voxels {
+                let dot_vox::Voxel { x, y, z, i } = *voxel;
+                let [x, y, z] = placed.transform.place(model.size, (x, y, z));
+                let pos = WorldPosition(
+                    (x - min[0]) as u32,
+                    (z - min[2]) as u32,
+                    (y - min[1]) as u32,
+                );
+                res.set(
+                    pos,
+                    Block::from_color(self.palette[i as usize])
+                        .with_material(self.materials[i as usize]),
+                );
+            }
+        }
+        Ok(res)
+    }
+}
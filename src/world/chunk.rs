@@ -0,0 +1,229 @@
+use std::{mem::size_of, str::FromStr};
+
+use super::{palette::PaletteBlocks, Block, SolidBlock, WorldDimension, WorldPosition};
+
+pub const CHUNK_SIZE: u32 = 32;
+pub const CHUNK_DIMS: WorldDimension = WorldDimension(CHUNK_SIZE, CHUNK_SIZE, CHUNK_SIZE);
+const CHUNK_VOLUME: usize = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageMode {
+    /// One `Block` per cell.
+    Dense,
+    /// A per-chunk palette of blocks, with cells packed as indices into it.
+    Palette,
+}
+
+impl FromStr for StorageMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dense" => Ok(Self::Dense),
+            "palette" => Ok(Self::Palette),
+            _ => Err(format!(
+                "unknown storage `{}`, expected dense or palette",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ChunkPosition(pub u32, pub u32, pub u32);
+
+impl ChunkPosition {
+    #[inline(always)]
+    pub fn split(WorldPosition(x, y, z): WorldPosition) -> (Self, WorldPosition) {
+        (
+            Self(x / CHUNK_SIZE, y / CHUNK_SIZE, z / CHUNK_SIZE),
+            WorldPosition(x % CHUNK_SIZE, y % CHUNK_SIZE, z % CHUNK_SIZE),
+        )
+    }
+
+    #[inline(always)]
+    pub fn origin(self) -> WorldPosition {
+        self.join(WorldPosition(0, 0, 0))
+    }
+
+    #[inline(always)]
+    pub fn join(self, WorldPosition(x, y, z): WorldPosition) -> WorldPosition {
+        let ChunkPosition(cx, cy, cz) = self;
+        WorldPosition(
+            cx * CHUNK_SIZE + x,
+            cy * CHUNK_SIZE + y,
+            cz * CHUNK_SIZE + z,
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Storage {
+    Dense(Box<[Block]>),
+    Palette(PaletteBlocks),
+}
+
+#[derive(Debug, Clone)]
+pub struct Chunk {
+    storage: Storage,
+    solid: usize,
+}
+
+impl Chunk {
+    pub fn new(mode: StorageMode) -> Self {
+        let storage = match mode {
+            StorageMode::Dense => {
+                Storage::Dense(vec![Block::Empty; CHUNK_VOLUME].into_boxed_slice())
+            }
+            StorageMode::Palette => Storage::Palette(PaletteBlocks::new(CHUNK_VOLUME)),
+        };
+        Self { storage, solid: 0 }
+    }
+
+    pub fn mode(&self) -> StorageMode {
+        match self.storage {
+            Storage::Dense(_) => StorageMode::Dense,
+            Storage::Palette(_) => StorageMode::Palette,
+        }
+    }
+
+    pub fn with_mode(&self, mode: StorageMode) -> Self {
+        let mut res = Self::new(mode);
+        for (local, blk) in self.iter() {
+            res.set(local, Block::Solid(*blk));
+        }
+        res
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.solid == 0
+    }
+
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.solid
+    }
+
+    #[inline(always)]
+    fn block(&self, i: usize) -> &Block {
+        match &self.storage {
+            Storage::Dense(blocks) => &blocks[i],
+            Storage::Palette(blocks) => blocks.get(i),
+        }
+    }
+
+    #[inline(always)]
+    pub fn get(&self, local: WorldPosition) -> &Block {
+        self.block(CHUNK_DIMS.idx(local))
+    }
+
+    pub fn set(&mut self, local: WorldPosition, blk: Block) -> Block {
+        let i = CHUNK_DIMS.idx(local);
+        let old = match &mut self.storage {
+            Storage::Dense(blocks) => std::mem::replace(&mut blocks[i], blk),
+            Storage::Palette(blocks) => blocks.set(i, blk),
+        };
+        match (old, blk) {
+            (Block::Empty, Block::Solid(_)) => self.solid += 1,
+            (Block::Solid(_), Block::Empty) => self.solid -= 1,
+            _ => (),
+        }
+        old
+    }
+
+    pub fn iter(&self) -> impl '_ + Iterator<Item = (WorldPosition, &SolidBlock)> {
+        (0..CHUNK_VOLUME).filter_map(move |i| match self.block(i) {
+            Block::Empty => None,
+            Block::Solid(blk) => Some((CHUNK_DIMS.pos(i), blk)),
+        })
+    }
+
+    pub fn bits_per_block(&self) -> u32 {
+        match &self.storage {
+            Storage::Dense(_) => (size_of::<Block>() * 8) as u32,
+            Storage::Palette(blocks) => blocks.bits(),
+        }
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        size_of::<Self>()
+            + match &self.storage {
+                Storage::Dense(blocks) => blocks.len() * size_of::<Block>(),
+                Storage::Palette(blocks) => blocks.memory_usage(),
+            }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::World;
+    use super::*;
+
+    fn cells(chunk: &Chunk) -> Vec<Block> {
+        (0..CHUNK_VOLUME).map(|i| *chunk.block(i)).collect()
+    }
+
+    fn blocks(world: &World) -> Vec<([u32; 3], SolidBlock)> {
+        let mut res: Vec<_> = world
+            .iter()
+            .map(|(WorldPosition(x, y, z), blk)| ([x, y, z], *blk))
+            .collect();
+        res.sort();
+        res
+    }
+
+    #[test]
+    fn storage_modes_agree() {
+        let mut dense = Chunk::new(StorageMode::Dense);
+        let mut palette = Chunk::new(StorageMode::Palette);
+        let mut state = 0x1234_5678u32;
+        for _ in 0..10_000 {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            let local = CHUNK_DIMS.pos(state as usize % CHUNK_VOLUME);
+            let blk = match state >> 24 {
+                0..=63 => Block::Empty,
+                value => Block::solid(value as u8, 0, 0),
+            };
+            assert_eq!(dense.set(local, blk), palette.set(local, blk));
+            assert_eq!(dense.len(), palette.len());
+        }
+        assert_eq!(cells(&dense), cells(&palette));
+        assert!(dense.iter().eq(palette.iter()));
+
+        let converted = dense.with_mode(StorageMode::Palette);
+        assert_eq!(converted.mode(), StorageMode::Palette);
+        assert_eq!(converted.len(), dense.len());
+        assert_eq!(cells(&converted), cells(&dense));
+        let back = converted.with_mode(StorageMode::Dense);
+        assert_eq!(back.mode(), StorageMode::Dense);
+        assert_eq!(cells(&back), cells(&dense));
+    }
+
+    #[test]
+    fn set_storage_keeps_every_block() {
+        let mut world = World::from_vox(include_bytes!("../../assets/room.vox")).unwrap();
+        let expected = blocks(&world);
+        let dense_bytes = world.memory_usage().bytes;
+
+        world.set_storage(StorageMode::Palette);
+        assert_eq!(blocks(&world), expected);
+        let usage = world.memory_usage();
+        assert_eq!(usage.storage, StorageMode::Palette);
+        assert_eq!(usage.blocks, expected.len());
+        assert!(usage.bytes < dense_bytes);
+
+        // Chunks created after the switch use the new storage as well.
+        let WorldDimension(x, y, z) = world.dims();
+        let far = WorldPosition(x + CHUNK_SIZE, y, z);
+        world.set(far, Block::solid(1, 2, 3));
+        assert!(world.memory_usage().max_bits_per_block < 64);
+        world.set(far, Block::Empty);
+
+        world.set_storage(StorageMode::Dense);
+        assert_eq!(blocks(&world), expected);
+        assert_eq!(world.memory_usage().storage, StorageMode::Dense);
+    }
+}
@@ -0,0 +1,561 @@
+use std::{
+    collections::HashMap,
+    fmt, io,
+    mem::size_of,
+    ops::Index,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::vox;
+
+mod chunk;
+mod octree;
+mod palette;
+
+use chunk::Chunk;
+pub use chunk::{ChunkPosition, StorageMode, CHUNK_SIZE};
+pub use octree::Octree;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MaterialKind {
+    Diffuse,
+    Metal,
+    Glass,
+    Emissive,
+    Blend,
+    Media,
+}
+
+impl MaterialKind {
+    pub fn from_vox(name: &str) -> Self {
+        match name {
+            "_metal" => Self::Metal,
+            "_glass" => Self::Glass,
+            "_emit" => Self::Emissive,
+            "_blend" => Self::Blend,
+            "_media" => Self::Media,
+            _ => Self::Diffuse,
+        }
+    }
+
+    pub fn to_vox(self) -> &'static str {
+        match self {
+            Self::Diffuse => "_diffuse",
+            Self::Metal => "_metal",
+            Self::Glass => "_glass",
+            Self::Emissive => "_emit",
+            Self::Blend => "_blend",
+            Self::Media => "_media",
+        }
+    }
+}
+
+// Stored quantised so blocks stay `Eq + Hash`; IOR keeps two decimals
+// above 1.0, the other properties are fractions in 0..=1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Material {
+    kind: MaterialKind,
+    roughness: u8,
+    emission: u8,
+    transparency: u8,
+    ior: u8,
+}
+
+fn quantise(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self::new(MaterialKind::Diffuse, 0.1, 0.0, 0.0, 1.3)
+    }
+}
+
+impl Material {
+    pub fn new(
+        kind: MaterialKind,
+        roughness: f32,
+        emission: f32,
+        transparency: f32,
+        ior: f32,
+    ) -> Self {
+        Self {
+            kind,
+            roughness: quantise(roughness),
+            emission: quantise(emission),
+            transparency: quantise(transparency),
+            ior: ((ior - 1.0) * 100.0).round().clamp(0.0, 255.0) as u8,
+        }
+    }
+
+    pub fn kind(&self) -> MaterialKind {
+        self.kind
+    }
+
+    pub fn roughness(&self) -> f32 {
+        self.roughness as f32 / 255.0
+    }
+
+    pub fn emission(&self) -> f32 {
+        self.emission as f32 / 255.0
+    }
+
+    pub fn transparency(&self) -> f32 {
+        self.transparency as f32 / 255.0
+    }
+
+    pub fn ior(&self) -> f32 {
+        1.0 + self.ior as f32 / 100.0
+    }
+}
+
+impl From<&Material> for [f32; 4] {
+    fn from(material: &Material) -> Self {
+        [
+            material.kind as u32 as f32,
+            material.roughness(),
+            material.emission(),
+            material.transparency(),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SolidBlock(u8, u8, u8, Material);
+
+impl SolidBlock {
+    #[inline(always)]
+    pub fn rgb(&self) -> (u8, u8, u8) {
+        (self.0, self.1, self.2)
+    }
+
+    #[inline(always)]
+    pub fn material(&self) -> &Material {
+        &self.3
+    }
+}
+
+impl From<&SolidBlock> for [f32; 3] {
+    fn from(blk: &SolidBlock) -> Self {
+        [
+            blk.0 as f32 / 255.0,
+            blk.1 as f32 / 255.0,
+            blk.2 as f32 / 255.0,
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Block {
+    Empty,
+    Solid(SolidBlock),
+}
+
+impl Default for Block {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::Empty
+    }
+}
+
+impl Block {
+    #[inline(always)]
+    pub fn solid(r: u8, g: u8, b: u8) -> Self {
+        Self::Solid(SolidBlock(r, g, b, Material::default()))
+    }
+
+    #[inline(always)]
+    pub fn with_material(self, material: Material) -> Self {
+        match self {
+            Self::Empty => Self::Empty,
+            Self::Solid(SolidBlock(r, g, b, _)) => Self::Solid(SolidBlock(r, g, b, material)),
+        }
+    }
+
+    #[inline(always)]
+    pub fn from_color(color: u32) -> Self {
+        let (b, g, r) = (color >> 16u32 & 0xFF, color >> 8u32 & 0xFF, color & 0xFF);
+        Self::solid(r as u8, g as u8, b as u8)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorldDimension(pub u32, pub u32, pub u32);
+
+impl WorldDimension {
+    pub fn idx(&self, index: WorldPosition) -> usize {
+        (index.0 as usize)
+            + (self.0 as usize) * ((index.1 as usize) + (self.1 as usize) * (index.2 as usize))
+    }
+
+    pub fn pos(&self, index: usize) -> WorldPosition {
+        let WorldDimension(width, height, _) = *self;
+        let (width, height) = (width as usize, height as usize);
+        WorldPosition(
+            (index % width) as u32,
+            (index / width % height) as u32,
+            (index / width / height) as u32,
+        )
+    }
+}
+
+impl From<(u32, u32, u32)> for WorldDimension {
+    fn from((x, y, z): (u32, u32, u32)) -> Self {
+        Self(x, y, z)
+    }
+}
+
+// Blocks live in fixed-size chunks keyed by chunk coordinate; chunks with
+// no solid blocks are never allocated.
+#[derive(Debug)]
+pub struct World {
+    chunks: HashMap<ChunkPosition, Chunk>,
+    dims: WorldDimension,
+    storage: StorageMode,
+    id: u64,
+    generation: u64,
+    // Generation of the last edit affecting each chunk, kept after the
+    // chunk itself is dropped so consumers still see it was cleared.
+    modified: HashMap<ChunkPosition, u64>,
+}
+
+// Shared by every world so a (id, generation) pair never repeats.
+static GENERATION: AtomicU64 = AtomicU64::new(1);
+
+fn next_generation() -> u64 {
+    GENERATION.fetch_add(1, Ordering::Relaxed)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryUsage {
+    pub storage: StorageMode,
+    pub chunks: usize,
+    pub blocks: usize,
+    pub max_bits_per_block: u32,
+    pub bytes: usize,
+    /// What a single flat `Vec<Block>` over the world dimensions would take.
+    pub dense_bytes: usize,
+}
+
+impl fmt::Display for MemoryUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} storage: {} blocks in {} chunks, {} bytes (at most {} bits per block), \
+             dense layout would take {} bytes ({:.1}%)",
+            self.storage,
+            self.blocks,
+            self.chunks,
+            self.bytes,
+            self.max_bits_per_block,
+            self.dense_bytes,
+            self.bytes as f64 * 100.0 / self.dense_bytes.max(1) as f64,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorldPosition(pub u32, pub u32, pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    North,
+    South,
+    East,
+    West,
+    Up,
+    Down,
+}
+
+impl From<(u32, u32, u32)> for WorldPosition {
+    fn from((x, y, z): (u32, u32, u32)) -> Self {
+        Self(x, y, z)
+    }
+}
+
+impl From<WorldPosition> for [f32; 3] {
+    fn from(WorldPosition(x, y, z): WorldPosition) -> Self {
+        [x as f32, y as f32, z as f32]
+    }
+}
+
+impl From<Direction> for u32 {
+    fn from(direction: Direction) -> Self {
+        match direction {
+            Direction::North => 0u32,
+            Direction::South => 1u32,
+            Direction::East => 2u32,
+            Direction::West => 3u32,
+            Direction::Up => 4u32,
+            Direction::Down => 5u32,
+        }
+    }
+}
+
+impl From<u32> for Direction {
+    fn from(val: u32) -> Self {
+        match val {
+            0u32 => Direction::North,
+            1u32 => Direction::South,
+            2u32 => Direction::East,
+            3u32 => Direction::West,
+            4u32 => Direction::Up,
+            5u32 => Direction::Down,
+            _ => panic!("unexpected value"),
+        }
+    }
+}
+
+impl Direction {
+    pub fn iter() -> impl Iterator<Item = Direction> {
+        (0..6).map(From::<u32>::from)
+    }
+
+    /// Unit step towards the neighbour on this side.
+    pub fn offset(self) -> [i32; 3] {
+        match self {
+            Direction::North => [0, 0, -1],
+            Direction::South => [0, 0, 1],
+            Direction::East => [1, 0, 0],
+            Direction::West => [-1, 0, 0],
+            Direction::Up => [0, 1, 0],
+            Direction::Down => [0, -1, 0],
+        }
+    }
+
+    pub fn apply(
+        self,
+        WorldDimension(mx, my, mz): WorldDimension,
+        WorldPosition(x, y, z): WorldPosition,
+    ) -> Option<WorldPosition> {
+        match self {
+            Direction::North => {
+                if z == 0 {
+                    None
+                } else {
+                    Some(WorldPosition(x, y, z - 1))
+                }
+            }
+            Direction::South => {
+                if z == mz - 1 {
+                    None
+                } else {
+                    Some(WorldPosition(x, y, z + 1))
+                }
+            }
+            Direction::East => {
+                if x == mx - 1 {
+                    None
+                } else {
+                    Some(WorldPosition(x + 1, y, z))
+                }
+            }
+            Direction::West => {
+                if x == 0 {
+                    None
+                } else {
+                    Some(WorldPosition(x - 1, y, z))
+                }
+            }
+            Direction::Up => {
+                if y == my - 1 {
+                    None
+                } else {
+                    Some(WorldPosition(x, y + 1, z))
+                }
+            }
+            Direction::Down => {
+                if y == 0 {
+                    None
+                } else {
+                    Some(WorldPosition(x, y - 1, z))
+                }
+            }
+        }
+    }
+}
+
+impl Index<WorldPosition> for World {
+    type Output = Block;
+
+    #[inline(always)]
+    fn index(&self, index: WorldPosition) -> &Self::Output {
+        let (chunk, local) = ChunkPosition::split(index);
+        match self.chunks.get(&chunk) {
+            Some(chunk) => chunk.get(local),
+            None => &Block::Empty,
+        }
+    }
+}
+
+impl World {
+    pub fn dims(&self) -> WorldDimension {
+        self.dims
+    }
+
+    #[inline(always)]
+    pub fn test(&self, index: WorldPosition) -> bool {
+        self[index] != Block::Empty
+    }
+
+    /// Stores `blk` at `index` and returns the block it replaced. Setting a
+    /// solid block outside the current dimensions grows the world to fit.
+    pub fn set(&mut self, index: WorldPosition, blk: Block) -> Block {
+        let (chunk_pos, local) = ChunkPosition::split(index);
+        let old = if blk == Block::Empty {
+            match self.chunks.get_mut(&chunk_pos) {
+                Some(chunk) => {
+                    let old = chunk.set(local, blk);
+                    if chunk.is_empty() {
+                        self.chunks.remove(&chunk_pos);
+                    }
+                    old
+                }
+                None => Block::Empty,
+            }
+        } else {
+            let WorldPosition(x, y, z) = index;
+            let WorldDimension(dx, dy, dz) = self.dims;
+            self.dims = WorldDimension(dx.max(x + 1), dy.max(y + 1), dz.max(z + 1));
+            let storage = self.storage;
+            self.chunks
+                .entry(chunk_pos)
+                .or_insert_with(|| Chunk::new(storage))
+                .set(local, blk)
+        };
+        if old != blk {
+            self.touch(chunk_pos, local);
+        }
+        old
+    }
+
+    // Faces on a chunk border depend on the neighbouring chunks, and the
+    // ambient occlusion of their corners on the diagonal ones too, so an
+    // edit there dirties every chunk it touches.
+    fn touch(&mut self, chunk: ChunkPosition, WorldPosition(x, y, z): WorldPosition) {
+        let generation = next_generation();
+        self.generation = generation;
+        let ChunkPosition(cx, cy, cz) = chunk;
+        let last = CHUNK_SIZE - 1;
+        let span = |local: u32, chunk: u32| {
+            let low = if local == 0 && chunk > 0 {
+                chunk - 1
+            } else {
+                chunk
+            };
+            let high = if local == last { chunk + 1 } else { chunk };
+            low..=high
+        };
+        for nx in span(x, cx) {
+            for ny in span(y, cy) {
+                for nz in span(z, cz) {
+                    self.modified.insert(ChunkPosition(nx, ny, nz), generation);
+                }
+            }
+        }
+    }
+
+    /// Unique for every `World` ever created.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Changes whenever a block is set to a different value.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Chunks whose contents, or whose border neighbours, changed after
+    /// `generation`. Pass 0 to get every chunk that was ever filled.
+    pub fn changes_since(&self, generation: u64) -> impl '_ + Iterator<Item = ChunkPosition> {
+        self.modified
+            .iter()
+            .filter(move |(_, modified)| **modified > generation)
+            .map(|(pos, _)| *pos)
+    }
+
+    pub fn iter_chunk(
+        &self,
+        chunk_pos: ChunkPosition,
+    ) -> impl '_ + Iterator<Item = (WorldPosition, &SolidBlock)> {
+        self.chunks
+            .get(&chunk_pos)
+            .into_iter()
+            .flat_map(move |chunk| {
+                chunk
+                    .iter()
+                    .map(move |(local, blk)| (chunk_pos.join(local), blk))
+            })
+    }
+
+    /// Converts every chunk to `storage`; chunks created later use it too.
+    pub fn set_storage(&mut self, storage: StorageMode) {
+        self.storage = storage;
+        for chunk in self.chunks.values_mut() {
+            if chunk.mode() != storage {
+                *chunk = chunk.with_mode(storage);
+            }
+        }
+    }
+
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let WorldDimension(x, y, z) = self.dims;
+        MemoryUsage {
+            storage: self.storage,
+            chunks: self.chunks.len(),
+            blocks: self.chunks.values().map(Chunk::len).sum(),
+            max_bits_per_block: self
+                .chunks
+                .values()
+                .map(Chunk::bits_per_block)
+                .max()
+                .unwrap_or(0),
+            bytes: size_of::<Self>()
+                + self.chunks.capacity() * size_of::<(ChunkPosition, Chunk)>()
+                + self.chunks.values().map(Chunk::memory_usage).sum::<usize>(),
+            dense_bytes: size_of::<Self>()
+                + (x as usize) * (y as usize) * (z as usize) * size_of::<Block>(),
+        }
+    }
+
+    pub fn iter<'a>(&'a self) -> impl 'a + Iterator<Item = (WorldPosition, &'a SolidBlock)> {
+        self.chunks.iter().flat_map(|(chunk_pos, chunk)| {
+            chunk
+                .iter()
+                .map(move |(local, blk)| (chunk_pos.join(local), blk))
+        })
+    }
+
+    /// Smallest box holding every solid block, as its minimum and maximum
+    /// corners in world units, or `None` if the world is empty.
+    pub fn bounds(&self) -> Option<([u32; 3], [u32; 3])> {
+        self.iter()
+            .fold(None, |bounds, (WorldPosition(x, y, z), _)| {
+                let (min, max) = bounds.unwrap_or(([x, y, z], [x + 1, y + 1, z + 1]));
+                Some((
+                    [min[0].min(x), min[1].min(y), min[2].min(z)],
+                    [max[0].max(x + 1), max[1].max(y + 1), max[2].max(z + 1)],
+                ))
+            })
+    }
+
+    pub fn new<DIMS: Into<WorldDimension>>(dims: DIMS) -> Self {
+        let id = next_generation();
+        Self {
+            chunks: HashMap::new(),
+            dims: dims.into(),
+            storage: StorageMode::Dense,
+            id,
+            generation: id,
+            modified: HashMap::new(),
+        }
+    }
+
+    pub fn from_vox(data: &[u8]) -> Result<Self, vox::VoxError> {
+        vox::load(data)
+    }
+
+    pub fn write_vox<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        vox::save(self, writer)
+    }
+}
@@ -0,0 +1,644 @@
+use std::collections::HashMap;
+
+use super::{Block, Direction, SolidBlock, World, WorldDimension, WorldPosition};
+
+// Children are ordered by octant: bit 0 selects the upper half along X,
+// bit 1 along Y and bit 2 along Z.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Leaf(Block),
+    Branch(Box<[Node; 8]>),
+}
+
+#[inline(always)]
+fn octant(pos: [u32; 3], origin: [u32; 3], half: u32) -> usize {
+    ((pos[0] - origin[0] >= half) as usize)
+        | ((pos[1] - origin[1] >= half) as usize) << 1
+        | ((pos[2] - origin[2] >= half) as usize) << 2
+}
+
+#[inline(always)]
+fn child_origin(index: usize, origin: [u32; 3], half: u32) -> [u32; 3] {
+    [
+        origin[0] + (index & 1) as u32 * half,
+        origin[1] + (index >> 1 & 1) as u32 * half,
+        origin[2] + (index >> 2 & 1) as u32 * half,
+    ]
+}
+
+impl Node {
+    fn set(&mut self, origin: [u32; 3], size: u32, pos: [u32; 3], blk: Block) -> Block {
+        if let Node::Leaf(leaf) = self {
+            if size == 1 || *leaf == blk {
+                return std::mem::replace(leaf, blk);
+            }
+            let leaf = *leaf;
+            *self = Node::Branch(Box::new(std::array::from_fn(|_| Node::Leaf(leaf))));
+        }
+        let half = size / 2;
+        let index = octant(pos, origin, half);
+        let old = match self {
+            Node::Branch(children) => {
+                children[index].set(child_origin(index, origin, half), half, pos, blk)
+            }
+            Node::Leaf(_) => unreachable!(),
+        };
+        self.collapse();
+        old
+    }
+
+    fn collapse(&mut self) {
+        if let Node::Branch(children) = self {
+            if let Node::Leaf(first) = children[0] {
+                if children.iter().all(|child| *child == Node::Leaf(first)) {
+                    *self = Node::Leaf(first);
+                }
+            }
+        }
+    }
+
+    fn count_blocks(&self, volume: u64, counts: &mut HashMap<SolidBlock, u64>) {
+        match self {
+            Node::Leaf(Block::Empty) => (),
+            Node::Leaf(Block::Solid(blk)) => *counts.entry(*blk).or_default() += volume,
+            Node::Branch(children) => {
+                for child in children.iter() {
+                    child.count_blocks(volume / 8, counts);
+                }
+            }
+        }
+    }
+
+    #[cfg(test)]
+    fn count_nodes(&self) -> usize {
+        match self {
+            Node::Leaf(_) => 1,
+            Node::Branch(children) => 1 + children.iter().map(Node::count_nodes).sum::<usize>(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RayHit<'a> {
+    pub position: WorldPosition,
+    pub block: &'a SolidBlock,
+    /// Distance from the ray origin, in blocks.
+    pub distance: f32,
+    /// Face the ray entered through, or `None` if it started inside the block.
+    pub face: Option<Direction>,
+}
+
+/// Sparse voxel octree over a cube of `2^depth` blocks; subtrees holding a
+/// single block value are collapsed into one leaf.
+#[derive(Debug, Clone)]
+pub struct Octree {
+    root: Node,
+    depth: u32,
+    dims: WorldDimension,
+}
+
+impl Octree {
+    pub fn new<DIMS: Into<WorldDimension>>(dims: DIMS) -> Self {
+        let dims = dims.into();
+        let WorldDimension(x, y, z) = dims;
+        Self {
+            root: Node::Leaf(Block::Empty),
+            depth: x.max(y).max(z).max(1).next_power_of_two().trailing_zeros(),
+            dims,
+        }
+    }
+
+    pub fn from_world(world: &World) -> Self {
+        let mut res = Self::new(world.dims());
+        for (pos, blk) in world.iter() {
+            res.set(pos, Block::Solid(*blk));
+        }
+        res
+    }
+
+    pub fn to_world(&self) -> World {
+        let mut res = World::new(self.dims);
+        for (pos, blk) in self.iter() {
+            res.set(pos, Block::Solid(*blk));
+        }
+        res
+    }
+
+    #[inline(always)]
+    fn size(&self) -> u32 {
+        1 << self.depth
+    }
+
+    /// Returns the leaf holding `pos` together with its origin and size.
+    fn leaf(&self, WorldPosition(x, y, z): WorldPosition) -> (&Block, [u32; 3], u32) {
+        let pos = [x, y, z];
+        let (mut node, mut origin, mut size) = (&self.root, [0; 3], self.size());
+        loop {
+            match node {
+                Node::Leaf(blk) => return (blk, origin, size),
+                Node::Branch(children) => {
+                    let half = size / 2;
+                    let index = octant(pos, origin, half);
+                    node = &children[index];
+                    origin = child_origin(index, origin, half);
+                    size = half;
+                }
+            }
+        }
+    }
+
+    pub fn get(&self, pos: WorldPosition) -> &Block {
+        let WorldPosition(x, y, z) = pos;
+        let WorldDimension(dx, dy, dz) = self.dims;
+        if x >= dx || y >= dy || z >= dz {
+            return &Block::Empty;
+        }
+        self.leaf(pos).0
+    }
+
+    #[inline(always)]
+    pub fn test(&self, pos: WorldPosition) -> bool {
+        *self.get(pos) != Block::Empty
+    }
+
+    /// Stores `blk` at `pos` and returns the block it replaced, growing the
+    /// tree when `pos` lies outside it.
+    pub fn set(&mut self, pos: WorldPosition, blk: Block) -> Block {
+        let WorldPosition(x, y, z) = pos;
+        if blk == Block::Empty && !self.test(pos) {
+            return Block::Empty;
+        }
+        while x.max(y).max(z) >= self.size() {
+            let root = std::mem::replace(&mut self.root, Node::Leaf(Block::Empty));
+            let mut children: [Node; 8] = std::array::from_fn(|_| Node::Leaf(Block::Empty));
+            children[0] = root;
+            self.root = Node::Branch(Box::new(children));
+            self.root.collapse();
+            self.depth += 1;
+        }
+        let WorldDimension(dx, dy, dz) = self.dims;
+        self.dims = WorldDimension(dx.max(x + 1), dy.max(y + 1), dz.max(z + 1));
+        let size = self.size();
+        self.root.set([0; 3], size, [x, y, z], blk)
+    }
+
+    pub fn iter(&self) -> RegionIter<'_> {
+        let WorldDimension(x, y, z) = self.dims;
+        self.region(WorldPosition(0, 0, 0), WorldPosition(x, y, z))
+    }
+
+    /// Iterates the solid blocks in the half-open box `from..to`, skipping
+    /// empty subtrees without visiting their cells.
+    pub fn region(&self, from: WorldPosition, to: WorldPosition) -> RegionIter<'_> {
+        let WorldDimension(dx, dy, dz) = self.dims;
+        RegionIter {
+            stack: vec![(&self.root, [0; 3], self.size())],
+            min: [from.0, from.1, from.2],
+            max: [to.0.min(dx), to.1.min(dy), to.2.min(dz)],
+            leaf: None,
+        }
+    }
+
+    /// Marches a ray through the tree, stepping over whole empty subtrees
+    /// at a time, and returns the first solid block it hits.
+    pub fn raycast(
+        &self,
+        origin: [f32; 3],
+        direction: [f32; 3],
+        max_distance: f32,
+    ) -> Option<RayHit<'_>> {
+        let length = direction.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if length == 0.0 {
+            return None;
+        }
+        let direction = direction.map(|v| v / length);
+        let WorldDimension(dx, dy, dz) = self.dims;
+        let dims = [dx, dy, dz];
+        if dims.contains(&0) {
+            return None;
+        }
+
+        let (mut t, mut t_exit, mut axis) = (0.0f32, max_distance, None);
+        for a in 0..3 {
+            if direction[a] == 0.0 {
+                if origin[a] < 0.0 || origin[a] >= dims[a] as f32 {
+                    return None;
+                }
+                continue;
+            }
+            let t0 = -origin[a] / direction[a];
+            let t1 = (dims[a] as f32 - origin[a]) / direction[a];
+            let (near, far) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+            if near > t {
+                t = near;
+                axis = Some(a);
+            }
+            t_exit = t_exit.min(far);
+        }
+        if t > t_exit {
+            return None;
+        }
+
+        let mut cell = [0u32; 3];
+        for a in 0..3 {
+            let p = (origin[a] + direction[a] * t).floor();
+            cell[a] = if Some(a) == axis {
+                if direction[a] > 0.0 {
+                    0
+                } else {
+                    dims[a] - 1
+                }
+            } else {
+                (p.max(0.0) as u32).min(dims[a] - 1)
+            };
+        }
+
+        loop {
+            let (blk, node, size) = self.leaf(WorldPosition(cell[0], cell[1], cell[2]));
+            if let Block::Solid(blk) = blk {
+                return Some(RayHit {
+                    position: WorldPosition(cell[0], cell[1], cell[2]),
+                    block: blk,
+                    distance: t,
+                    face: axis.map(|a| entry_face(a, direction[a])),
+                });
+            }
+
+            let (mut next, mut next_axis) = (f32::INFINITY, 0);
+            for a in 0..3 {
+                let bound = if direction[a] > 0.0 {
+                    (node[a] + size) as f32
+                } else if direction[a] < 0.0 {
+                    node[a] as f32
+                } else {
+                    continue;
+                };
+                let t_axis = (bound - origin[a]) / direction[a];
+                if t_axis < next {
+                    next = t_axis;
+                    next_axis = a;
+                }
+            }
+            if next > t_exit {
+                return None;
+            }
+            t = next;
+            axis = Some(next_axis);
+
+            for a in 0..3 {
+                cell[a] = if a == next_axis {
+                    if direction[a] > 0.0 {
+                        node[a] + size
+                    } else if node[a] == 0 {
+                        return None;
+                    } else {
+                        node[a] - 1
+                    }
+                } else {
+                    let p = (origin[a] + direction[a] * t).floor().max(0.0) as u32;
+                    p.clamp(node[a], node[a] + size - 1)
+                };
+                if cell[a] >= dims[a] {
+                    return None;
+                }
+            }
+        }
+    }
+
+    /// Downsamples by `2^level` along each axis. A coarse cell is solid if
+    /// any block under it is, and takes the most common block by volume.
+    pub fn lod(&self, level: u32) -> Octree {
+        let scale = 1 << level.min(self.depth);
+        let WorldDimension(x, y, z) = self.dims;
+        let dims = [x.div_ceil(scale), y.div_ceil(scale), z.div_ceil(scale)];
+        let mut res = Octree::new((dims[0], dims[1], dims[2]));
+        self.lod_node(&self.root, [0; 3], self.size(), scale, dims, &mut res);
+        res
+    }
+
+    fn lod_node(
+        &self,
+        node: &Node,
+        origin: [u32; 3],
+        size: u32,
+        scale: u32,
+        dims: [u32; 3],
+        res: &mut Octree,
+    ) {
+        match node {
+            Node::Leaf(Block::Empty) => (),
+            Node::Leaf(blk) => {
+                let lo = origin.map(|v| v / scale);
+                let hi = [0, 1, 2].map(|a| ((origin[a] + size) / scale).min(dims[a]));
+                for z in lo[2]..hi[2] {
+                    for y in lo[1]..hi[1] {
+                        for x in lo[0]..hi[0] {
+                            res.set(WorldPosition(x, y, z), *blk);
+                        }
+                    }
+                }
+            }
+            Node::Branch(children) if size > scale => {
+                let half = size / 2;
+                for (index, child) in children.iter().enumerate() {
+                    let origin = child_origin(index, origin, half);
+                    self.lod_node(child, origin, half, scale, dims, res);
+                }
+            }
+            Node::Branch(_) => {
+                let mut counts = HashMap::new();
+                node.count_blocks(u64::from(size).pow(3), &mut counts);
+                let pos = origin.map(|v| v / scale);
+                if let Some((blk, _)) = counts.into_iter().max_by_key(|(blk, count)| (*count, *blk))
+                {
+                    if (0..3).all(|a| pos[a] < dims[a]) {
+                        res.set(WorldPosition(pos[0], pos[1], pos[2]), Block::Solid(blk));
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn entry_face(axis: usize, direction: f32) -> Direction {
+    match (axis, direction > 0.0) {
+        (0, true) => Direction::West,
+        (0, false) => Direction::East,
+        (1, true) => Direction::Down,
+        (1, false) => Direction::Up,
+        (_, true) => Direction::North,
+        (_, false) => Direction::South,
+    }
+}
+
+// A solid leaf clipped to the region: its block, the clipped bounds and
+// the next cell to yield.
+struct LeafCursor<'a> {
+    blk: &'a SolidBlock,
+    lo: [u32; 3],
+    hi: [u32; 3],
+    cursor: [u32; 3],
+}
+
+pub struct RegionIter<'a> {
+    stack: Vec<(&'a Node, [u32; 3], u32)>,
+    min: [u32; 3],
+    max: [u32; 3],
+    leaf: Option<LeafCursor<'a>>,
+}
+
+impl<'a> Iterator for RegionIter<'a> {
+    type Item = (WorldPosition, &'a SolidBlock);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(LeafCursor {
+                blk,
+                lo,
+                hi,
+                cursor,
+            }) = &mut self.leaf
+            {
+                if cursor[2] < hi[2] {
+                    let [x, y, z] = *cursor;
+                    cursor[0] += 1;
+                    if cursor[0] == hi[0] {
+                        cursor[0] = lo[0];
+                        cursor[1] += 1;
+                        if cursor[1] == hi[1] {
+                            cursor[1] = lo[1];
+                            cursor[2] += 1;
+                        }
+                    }
+                    return Some((WorldPosition(x, y, z), *blk));
+                }
+                self.leaf = None;
+            }
+
+            let (node, origin, size) = self.stack.pop()?;
+            let lo = [0, 1, 2].map(|a| origin[a].max(self.min[a]));
+            let hi = [0, 1, 2].map(|a| (origin[a] + size).min(self.max[a]));
+            if (0..3).any(|a| lo[a] >= hi[a]) {
+                continue;
+            }
+            match node {
+                Node::Leaf(Block::Empty) => (),
+                Node::Leaf(Block::Solid(blk)) => {
+                    self.leaf = Some(LeafCursor {
+                        blk,
+                        lo,
+                        hi,
+                        cursor: lo,
+                    })
+                }
+                Node::Branch(children) => {
+                    let half = size / 2;
+                    for (index, child) in children.iter().enumerate().rev() {
+                        self.stack
+                            .push((child, child_origin(index, origin, half), half));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn teapot() -> World {
+        World::from_vox(include_bytes!("../../assets/teapot.vox")).unwrap()
+    }
+
+    fn blocks<'a>(
+        iter: impl Iterator<Item = (WorldPosition, &'a SolidBlock)>,
+    ) -> Vec<([u32; 3], SolidBlock)> {
+        let mut res: Vec<_> = iter
+            .map(|(WorldPosition(x, y, z), blk)| ([x, y, z], *blk))
+            .collect();
+        res.sort();
+        res
+    }
+
+    // Deterministic xorshift, so failures reproduce.
+    struct Rng(u32);
+
+    impl Rng {
+        fn next(&mut self) -> f32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+            self.0 as f32 / u32::MAX as f32
+        }
+
+        fn range(&mut self, min: f32, max: f32) -> f32 {
+            min + (max - min) * self.next()
+        }
+    }
+
+    /// Cell by cell DDA over the dense world, to check the octree against.
+    fn dense_raycast(
+        world: &World,
+        origin: [f32; 3],
+        direction: [f32; 3],
+        max_distance: f32,
+    ) -> Option<(WorldPosition, f32, Option<Direction>)> {
+        let length = direction.iter().map(|v| v * v).sum::<f32>().sqrt();
+        let direction = direction.map(|v| v / length);
+        let WorldDimension(dx, dy, dz) = world.dims();
+        let dims = [dx as f32, dy as f32, dz as f32];
+        let (mut t, mut t_exit, mut axis) = (0.0f32, max_distance, None);
+        for a in 0..3 {
+            if direction[a] == 0.0 {
+                if origin[a] < 0.0 || origin[a] >= dims[a] {
+                    return None;
+                }
+                continue;
+            }
+            let t0 = -origin[a] / direction[a];
+            let t1 = (dims[a] - origin[a]) / direction[a];
+            if t0.min(t1) > t {
+                t = t0.min(t1);
+                axis = Some(a);
+            }
+            t_exit = t_exit.min(t0.max(t1));
+        }
+        if t > t_exit {
+            return None;
+        }
+        let mut cell = [0, 1, 2].map(|a| {
+            let p = (origin[a] + direction[a] * t).floor();
+            if Some(a) == axis {
+                if direction[a] > 0.0 {
+                    0
+                } else {
+                    dims[a] as i64 - 1
+                }
+            } else {
+                (p.max(0.0) as i64).min(dims[a] as i64 - 1)
+            }
+        });
+        loop {
+            if cell.iter().zip(dims).any(|(&c, d)| c < 0 || c >= d as i64) {
+                return None;
+            }
+            let pos = WorldPosition(cell[0] as u32, cell[1] as u32, cell[2] as u32);
+            if world.test(pos) {
+                return Some((pos, t, axis.map(|a| entry_face(a, direction[a]))));
+            }
+            let (next, next_axis) = (0..3)
+                .filter(|&a| direction[a] != 0.0)
+                .map(|a| {
+                    let bound = cell[a] as f32 + if direction[a] > 0.0 { 1.0 } else { 0.0 };
+                    ((bound - origin[a]) / direction[a], a)
+                })
+                .fold((f32::INFINITY, 0), |best, next| {
+                    if next.0 < best.0 {
+                        next
+                    } else {
+                        best
+                    }
+                });
+            if next > t_exit {
+                return None;
+            }
+            t = next;
+            axis = Some(next_axis);
+            cell[next_axis] += if direction[next_axis] > 0.0 { 1 } else { -1 };
+        }
+    }
+
+    #[test]
+    fn world_round_trip() {
+        let world = teapot();
+        let octree = Octree::from_world(&world);
+        assert_eq!(octree.dims, world.dims());
+        assert_eq!(blocks(octree.iter()), blocks(world.iter()));
+
+        let back = octree.to_world();
+        assert_eq!(back.dims(), world.dims());
+        assert_eq!(blocks(back.iter()), blocks(world.iter()));
+    }
+
+    #[test]
+    fn region_matches_filter() {
+        let world = teapot();
+        let octree = Octree::from_world(&world);
+        let WorldDimension(dx, dy, dz) = world.dims();
+        let boxes = [
+            ([0, 0, 0], [dx, dy, dz]),
+            ([3, 5, 7], [dx / 2, dy - 1, dz / 3 + 5]),
+            ([dx / 3, 0, dz / 4], [dx + 10, dy / 2, dz + 10]),
+            ([5, 5, 5], [5, 6, 7]),
+        ];
+        for (min, max) in boxes {
+            let expected = blocks(world.iter().filter(|(WorldPosition(x, y, z), _)| {
+                let pos = [*x, *y, *z];
+                (0..3).all(|a| min[a] <= pos[a] && pos[a] < max[a])
+            }));
+            let region = octree.region(
+                WorldPosition(min[0], min[1], min[2]),
+                WorldPosition(max[0], max[1], max[2]),
+            );
+            assert_eq!(blocks(region), expected, "{:?}..{:?}", min, max);
+        }
+    }
+
+    #[test]
+    fn clearing_collapses_to_one_leaf() {
+        let world = teapot();
+        let mut octree = Octree::from_world(&world);
+        assert!(octree.root.count_nodes() > 1);
+        for (pos, _) in world.iter() {
+            octree.set(pos, Block::Empty);
+        }
+        assert_eq!(octree.root.count_nodes(), 1);
+        assert_eq!(octree.iter().count(), 0);
+
+        // A filled, aligned cube collapses the same way.
+        let mut octree = Octree::new((4, 4, 4));
+        for z in 0..4 {
+            for y in 0..4 {
+                for x in 0..4 {
+                    octree.set(WorldPosition(x, y, z), Block::solid(1, 2, 3));
+                }
+            }
+        }
+        assert_eq!(octree.root.count_nodes(), 1);
+    }
+
+    #[test]
+    fn raycast_matches_dense_dda() {
+        let world = teapot();
+        let octree = Octree::from_world(&world);
+        let WorldDimension(dx, dy, dz) = world.dims();
+        let dims = [dx as f32, dy as f32, dz as f32];
+        let mut rng = Rng(0x2545_f491);
+        let mut hits = 0;
+        for _ in 0..2000 {
+            let origin = dims.map(|d| rng.range(-8.0, d + 8.0));
+            // Aim near the middle so most rays cross the model.
+            let aim = dims.map(|d| rng.range(d * 0.25, d * 0.75));
+            let direction = [0, 1, 2].map(|a| aim[a] - origin[a]);
+            let expected = dense_raycast(&world, origin, direction, 1000.0);
+            let actual = octree
+                .raycast(origin, direction, 1000.0)
+                .map(|hit| (hit.position, hit.distance, hit.face));
+            match (expected, actual) {
+                (None, None) => (),
+                (Some(expected), Some(actual)) => {
+                    hits += 1;
+                    assert_eq!(actual.0, expected.0, "{:?} -> {:?}", origin, direction);
+                    assert_eq!(actual.2, expected.2, "{:?} -> {:?}", origin, direction);
+                    assert!((actual.1 - expected.1).abs() < 1e-3);
+                }
+                _ => panic!(
+                    "{:?} -> {:?}: {:?} vs {:?}",
+                    origin, direction, expected, actual
+                ),
+            }
+        }
+        assert!(hits > 500, "only {} rays hit the model", hits);
+    }
+}
@@ -0,0 +1,223 @@
+use std::mem::size_of;
+
+use super::Block;
+
+// Cells are packed into `u64` words without straddling word boundaries, so
+// a word holds `64 / bits` cells. Entry 0 is always `Block::Empty`, which
+// lets an all-empty chunk get away with zero bits per cell.
+#[derive(Debug, Clone)]
+pub struct PaletteBlocks {
+    palette: Vec<Block>,
+    counts: Vec<u32>,
+    bits: u32,
+    words: Vec<u64>,
+    len: usize,
+}
+
+fn bits_for(entries: usize) -> u32 {
+    if entries <= 1 {
+        0
+    } else {
+        usize::BITS - (entries - 1).leading_zeros()
+    }
+}
+
+impl PaletteBlocks {
+    pub fn new(len: usize) -> Self {
+        Self {
+            palette: vec![Block::Empty],
+            counts: vec![len as u32],
+            bits: 0,
+            words: Vec::new(),
+            len,
+        }
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    #[inline(always)]
+    fn index(&self, i: usize) -> usize {
+        if self.bits == 0 {
+            return 0;
+        }
+        let per_word = (64 / self.bits) as usize;
+        let shift = (i % per_word) as u32 * self.bits;
+        ((self.words[i / per_word] >> shift) & ((1 << self.bits) - 1)) as usize
+    }
+
+    #[inline(always)]
+    fn store(&mut self, i: usize, index: usize) {
+        let per_word = (64 / self.bits) as usize;
+        let shift = (i % per_word) as u32 * self.bits;
+        let mask = ((1u64 << self.bits) - 1) << shift;
+        let word = &mut self.words[i / per_word];
+        *word = (*word & !mask) | ((index as u64) << shift);
+    }
+
+    fn repack(&mut self, bits: u32) {
+        let indices: Vec<_> = (0..self.len).map(|i| self.index(i)).collect();
+        let per_word = (64 / bits) as usize;
+        self.bits = bits;
+        self.words = vec![0; self.len.div_ceil(per_word)];
+        for (i, index) in indices.into_iter().enumerate() {
+            self.store(i, index);
+        }
+    }
+
+    // Reuse entries whose cells have all been overwritten before growing
+    // the palette, so repeated edits don't widen the packing forever.
+    fn entry(&mut self, blk: Block) -> usize {
+        if let Some(index) = self.palette.iter().position(|entry| *entry == blk) {
+            return index;
+        }
+        if let Some(index) = self.counts.iter().skip(1).position(|count| *count == 0) {
+            self.palette[index + 1] = blk;
+            return index + 1;
+        }
+        self.palette.push(blk);
+        self.counts.push(0);
+        let bits = bits_for(self.palette.len());
+        if bits != self.bits {
+            self.repack(bits);
+        }
+        self.palette.len() - 1
+    }
+
+    #[inline(always)]
+    pub fn get(&self, i: usize) -> &Block {
+        &self.palette[self.index(i)]
+    }
+
+    pub fn set(&mut self, i: usize, blk: Block) -> Block {
+        let old = self.index(i);
+        let prev = self.palette[old];
+        if prev == blk {
+            return prev;
+        }
+        // Release the cell first, so overwriting the last cell of a block
+        // can hand its entry straight to the new one.
+        self.counts[old] -= 1;
+        let index = self.entry(blk);
+        self.counts[index] += 1;
+        self.store(i, index);
+        prev
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        self.palette.capacity() * size_of::<Block>()
+            + self.counts.capacity() * size_of::<u32>()
+            + self.words.capacity() * size_of::<u64>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Deterministic xorshift, so failures reproduce.
+    fn xorshift(state: &mut u32) -> u32 {
+        *state ^= *state << 13;
+        *state ^= *state >> 17;
+        *state ^= *state << 5;
+        *state
+    }
+
+    fn block(i: u32) -> Block {
+        Block::solid(i as u8, (i >> 8) as u8, 7)
+    }
+
+    fn check(blocks: &PaletteBlocks, expected: &[Block]) {
+        for (i, blk) in expected.iter().enumerate() {
+            assert_eq!(blocks.get(i), blk, "cell {}", i);
+        }
+        for (index, count) in blocks.counts.iter().enumerate() {
+            let cells = (0..blocks.len)
+                .filter(|&i| blocks.index(i) == index)
+                .count();
+            assert_eq!(*count as usize, cells, "count of entry {}", index);
+        }
+    }
+
+    #[test]
+    fn bits_per_cell() {
+        let bits: Vec<_> = [0, 1, 2, 3, 4, 5, 16, 17, 256, 257]
+            .iter()
+            .map(|&entries| bits_for(entries))
+            .collect();
+        assert_eq!(bits, [0, 0, 1, 2, 2, 3, 4, 5, 8, 9]);
+    }
+
+    #[test]
+    fn empty_blocks_take_no_words() {
+        let blocks = PaletteBlocks::new(1000);
+        assert_eq!(blocks.bits(), 0);
+        assert!(blocks.words.is_empty());
+        check(&blocks, &[Block::Empty; 1000]);
+    }
+
+    #[test]
+    fn growing_repacks_every_cell() {
+        // 1000 cells don't divide evenly into words at most widths, so the
+        // last word is only partly used.
+        let mut blocks = PaletteBlocks::new(1000);
+        let mut expected = vec![Block::Empty; 1000];
+        for entries in 1..=300 {
+            let i = (entries as usize * 37) % expected.len();
+            expected[i] = block(entries);
+            blocks.set(i, block(entries));
+            assert_eq!(blocks.bits(), bits_for(blocks.palette.len()));
+            if entries.is_power_of_two() || entries == 300 {
+                check(&blocks, &expected);
+            }
+        }
+        assert_eq!(blocks.bits(), 9);
+        assert_eq!(blocks.words.len(), 1000usize.div_ceil(64 / 9));
+    }
+
+    #[test]
+    fn unused_entries_are_reused() {
+        let mut blocks = PaletteBlocks::new(64);
+        blocks.set(0, block(1));
+        blocks.set(1, block(2));
+        assert_eq!((blocks.palette.len(), blocks.bits()), (3, 2));
+
+        // Overwriting the only cell of block 1 frees its entry for block 3.
+        assert_eq!(blocks.set(0, block(3)), block(1));
+        assert_eq!(blocks.palette, [Block::Empty, block(3), block(2)]);
+        assert_eq!(blocks.bits(), 2);
+
+        // Clearing frees entries too, and setting the same block is a no-op.
+        blocks.set(1, Block::Empty);
+        blocks.set(2, block(4));
+        blocks.set(2, block(4));
+        assert_eq!(blocks.palette, [Block::Empty, block(3), block(4)]);
+        let mut expected = vec![Block::Empty; 64];
+        expected[0] = block(3);
+        expected[2] = block(4);
+        check(&blocks, &expected);
+    }
+
+    #[test]
+    fn random_edits_match_a_plain_array() {
+        let mut blocks = PaletteBlocks::new(4096);
+        let mut expected = vec![Block::Empty; 4096];
+        let mut state = 0x9e37_79b9;
+        for step in 0..20_000 {
+            let i = xorshift(&mut state) as usize % expected.len();
+            let value = xorshift(&mut state) % 48;
+            let blk = if value < 8 {
+                Block::Empty
+            } else {
+                block(value)
+            };
+            assert_eq!(blocks.set(i, blk), expected[i], "step {}", step);
+            expected[i] = blk;
+        }
+        check(&blocks, &expected);
+        // 40 blocks and empty never need more than 6 bits.
+        assert!(blocks.palette.len() <= 41);
+        assert_eq!(blocks.bits(), 6);
+    }
+}